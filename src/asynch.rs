@@ -0,0 +1,442 @@
+//! Async driver for embedded-hal-async
+//!
+//! The async driver only does the bus operations: init, flush and power
+//! management. Drawing happens on its [`Buffer`], which is a blocking
+//! [`crate::ST7306`] that isn't connected to the display. So all drawing
+//! functions, including the embedded-graphics support, are the same.
+//!
+//! ```ignore
+//! let mut display: ST7306<_, _, _, 60, 200> = ST7306::new(spi, dc, rst, ST7306Builder::new())?;
+//! display.init(&mut delay).await?;
+//! display.buffer_mut().fill_rect(0, 0, 100, 100, true);
+//! display.flush().await?;
+//! ```
+
+use embedded_hal::blocking::spi;
+use embedded_hal::digital::v2;
+use embedded_hal_1::digital::OutputPin;
+use embedded_hal_async::delay::DelayNs;
+use embedded_hal_async::spi::SpiDevice;
+
+use crate::instruction::Instruction;
+use crate::{
+    rle_runs, AddrWindow, CommandList, ConfigError, Error, FlushPlan, PowerMode, ST7306Builder,
+    RLE_CHUNK_CELLS,
+};
+
+/// Stand-in for the bus and pins of a [`Buffer`]
+///
+/// Every operation fails with [`Detached`], so calling a function of the
+/// buffer that talks to the display returns an error instead of silently
+/// doing nothing. Use the functions of the async driver for those.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct Detached;
+
+impl spi::Write<u8> for Detached {
+    type Error = Detached;
+
+    fn write(&mut self, _words: &[u8]) -> Result<(), Self::Error> {
+        Err(Detached)
+    }
+}
+
+impl v2::OutputPin for Detached {
+    type Error = Detached;
+
+    fn set_low(&mut self) -> Result<(), Self::Error> {
+        Err(Detached)
+    }
+
+    fn set_high(&mut self) -> Result<(), Self::Error> {
+        Err(Detached)
+    }
+}
+
+/// Framebuffer and pixel logic of the async driver
+pub type Buffer<const COLS: usize, const ROWS: usize> =
+    crate::ST7306<Detached, Detached, Detached, Detached, COLS, ROWS>;
+
+/// Async ST7306 driver
+///
+/// Uses an [`SpiDevice`], which controls the chip select pin itself.
+pub struct ST7306<SPI, DC, RST, const COLS: usize, const ROWS: usize>
+where
+    SPI: SpiDevice,
+    DC: OutputPin,
+    RST: OutputPin<Error = DC::Error>,
+{
+    /// SPI
+    pub spi: SPI,
+
+    /// Data/command pin.
+    pub dc: DC,
+
+    /// Reset pin.
+    pub rst: RST,
+
+    /// Framebuffer and state of the display
+    buffer: Buffer<COLS, ROWS>,
+}
+
+impl<SPI, DC, RST, const COLS: usize, const ROWS: usize> ST7306<SPI, DC, RST, COLS, ROWS>
+where
+    SPI: SpiDevice,
+    DC: OutputPin,
+    RST: OutputPin<Error = DC::Error>,
+{
+    /// Creates a new driver instance with the given configuration
+    pub fn new(spi: SPI, dc: DC, rst: RST, config: ST7306Builder) -> Result<Self, ConfigError> {
+        Ok(ST7306 {
+            spi,
+            dc,
+            rst,
+            buffer: config.build(Detached, Detached, Detached, Detached)?,
+        })
+    }
+
+    /// The framebuffer, to read pixels and state
+    pub fn buffer(&self) -> &Buffer<COLS, ROWS> {
+        &self.buffer
+    }
+
+    /// The framebuffer, to draw on
+    ///
+    /// Call [`Self::flush()`] afterwards to show the changes on the display.
+    pub fn buffer_mut(&mut self) -> &mut Buffer<COLS, ROWS> {
+        &mut self.buffer
+    }
+
+    /// Runs commands to initialize the display.
+    ///
+    /// The same sequence as [`crate::ST7306::init()`], but the delays yield
+    /// to the executor.
+    pub async fn init<DELAY>(
+        &mut self,
+        delay: &mut DELAY,
+    ) -> Result<(), Error<SPI::Error, DC::Error>>
+    where
+        DELAY: DelayNs,
+    {
+        if self.buffer.initialized {
+            return Err(Error::AlreadyInitialized);
+        }
+
+        if !self.buffer.external_reset {
+            self.hard_reset(delay).await?;
+        }
+        self.soft_reset(delay).await?;
+        self.load_config().await?;
+        self.power_on(delay).await?;
+
+        self.buffer.initialized = true;
+        Ok(())
+    }
+
+    /// Reset and initialize the display again, even if it already was
+    pub async fn reinit<DELAY>(
+        &mut self,
+        delay: &mut DELAY,
+    ) -> Result<(), Error<SPI::Error, DC::Error>>
+    where
+        DELAY: DelayNs,
+    {
+        self.buffer.initialized = false;
+        self.init(delay).await
+    }
+
+    /// Reset the controller with the SWRESET command
+    ///
+    /// See [`crate::ST7306::soft_reset()`].
+    pub async fn soft_reset<DELAY>(
+        &mut self,
+        delay: &mut DELAY,
+    ) -> Result<(), Error<SPI::Error, DC::Error>>
+    where
+        DELAY: DelayNs,
+    {
+        self.write_command(Instruction::SWRESET, &[]).await?;
+        delay
+            .delay_ms(self.buffer.timings.soft_reset_ms as u32)
+            .await;
+        self.buffer.reset_state();
+        Ok(())
+    }
+
+    /// Write the configuration registers
+    ///
+    /// See [`crate::ST7306::load_config()`].
+    pub async fn load_config(&mut self) -> Result<(), Error<SPI::Error, DC::Error>> {
+        let commands = self.buffer.config_commands();
+        self.write_commands(&commands).await
+    }
+
+    /// Wake the controller up and turn the display on
+    ///
+    /// See [`crate::ST7306::power_on()`].
+    pub async fn power_on<DELAY>(
+        &mut self,
+        delay: &mut DELAY,
+    ) -> Result<(), Error<SPI::Error, DC::Error>>
+    where
+        DELAY: DelayNs,
+    {
+        self.write_command(Instruction::SLPOUT, &[]).await?;
+        self.buffer.sleeping = false;
+        delay.delay_ms(self.buffer.timings.power_on_ms as u32).await;
+
+        let commands = self.buffer.power_on_commands();
+        self.write_commands(&commands).await?;
+        self.buffer.powered_on();
+        Ok(())
+    }
+
+    /// Flush the changed part of the framebuffer to the screen
+    ///
+    /// See [`crate::ST7306::flush()`].
+    pub async fn flush(&mut self) -> Result<(), Error<SPI::Error, DC::Error>> {
+        match self.buffer.flush_plan() {
+            FlushPlan::Skip => Ok(()),
+            FlushPlan::Full => self.flush_full().await,
+            FlushPlan::Cells(dirty) => {
+                self.flush_cells(dirty).await?;
+                self.buffer.dirty = None;
+                Ok(())
+            }
+        }
+    }
+
+    /// Flush the entire framebuffer to the screen
+    ///
+    /// See [`crate::ST7306::flush_full()`].
+    pub async fn flush_full(&mut self) -> Result<(), Error<SPI::Error, DC::Error>> {
+        if self.buffer.ram_window != Some(self.buffer.addr_window) {
+            self.write_full_window().await?;
+        }
+        self.write_command(Instruction::RAMWR, &[]).await?;
+        self.dc.set_high().map_err(Error::Pin)?;
+
+        let max_transfer = self.buffer.max_transfer;
+        let cells = self.buffer.framebuffer.as_flattened();
+        if self.buffer.rle_flush {
+            for (cell, run) in rle_runs(cells) {
+                let pattern = [cell; RLE_CHUNK_CELLS];
+                let data = pattern[..run].as_flattened();
+                self.spi.write(data).await.map_err(Error::Spi)?;
+            }
+        } else {
+            for chunk in cells.as_flattened().chunks(max_transfer) {
+                self.spi.write(chunk).await.map_err(Error::Spi)?;
+            }
+        }
+        self.buffer.flushed_full();
+        Ok(())
+    }
+
+    /// Send a window of framebuffer cells, inclusive
+    async fn flush_cells(&mut self, cells: AddrWindow) -> Result<(), Error<SPI::Error, DC::Error>> {
+        let window = self.buffer.cells_window(cells);
+        self.write_columns(window.col_start, window.col_end).await?;
+        self.write_rows(window.row_start, window.row_end).await?;
+        self.write_command(Instruction::RAMWR, &[]).await?;
+        self.dc.set_high().map_err(Error::Pin)?;
+
+        let max_transfer = self.buffer.max_transfer;
+        let (col_start, col_end) = (cells.col_start as usize, cells.col_end as usize);
+        for row in cells.row_start as usize..=cells.row_end as usize {
+            let data = self.buffer.framebuffer[row][col_start..=col_end].as_flattened();
+            for chunk in data.chunks(max_transfer) {
+                self.spi.write(chunk).await.map_err(Error::Spi)?;
+            }
+        }
+
+        // Full flushes expect the full window
+        self.write_full_window().await
+    }
+
+    /// Turn the display on or off
+    pub async fn on_off(&mut self, on: bool) -> Result<(), Error<SPI::Error, DC::Error>> {
+        if on {
+            self.write_command(Instruction::DISPON, &[]).await?;
+        } else {
+            self.write_command(Instruction::DISPOFF, &[]).await?;
+        }
+        self.buffer.display_on = on;
+        Ok(())
+    }
+
+    /// See [`crate::ST7306::standby()`].
+    pub async fn standby(&mut self) -> Result<(), Error<SPI::Error, DC::Error>> {
+        self.on_off(false).await
+    }
+
+    /// See [`crate::ST7306::wake()`].
+    pub async fn wake(&mut self) -> Result<(), Error<SPI::Error, DC::Error>> {
+        self.on_off(true).await
+    }
+
+    /// Have the display controller go into sleep mode
+    ///
+    /// See [`crate::ST7306::sleep_in()`].
+    pub async fn sleep_in<DELAY>(
+        &mut self,
+        delay: &mut DELAY,
+    ) -> Result<(), Error<SPI::Error, DC::Error>>
+    where
+        DELAY: DelayNs,
+    {
+        if self.buffer.power_mode == PowerMode::Lpm {
+            self.switch_mode(delay, PowerMode::Hpm).await?;
+            delay.delay_ms(self.buffer.timings.lpm_exit_ms as u32).await;
+        }
+        self.write_command(Instruction::SLPIN, &[]).await?;
+        delay.delay_ms(self.buffer.timings.sleep_in_ms as u32).await;
+        self.buffer.sleeping = true;
+        Ok(())
+    }
+
+    /// Wake the controller from sleep
+    pub async fn sleep_out<DELAY>(
+        &mut self,
+        delay: &mut DELAY,
+    ) -> Result<(), Error<SPI::Error, DC::Error>>
+    where
+        DELAY: DelayNs,
+    {
+        self.write_command(Instruction::SLPOUT, &[]).await?;
+        delay
+            .delay_ms(self.buffer.timings.sleep_out_ms as u32)
+            .await;
+        self.buffer.sleeping = false;
+        Ok(())
+    }
+
+    /// Switch between high and low power mode
+    ///
+    /// Waits for [`crate::Timings::settle_frames`] frames at the new frame
+    /// rate like [`crate::ST7306::switch_mode()`], up to 8 seconds at
+    /// [`crate::LpmFps::Quarter`]. Other tasks keep running meanwhile, set
+    /// `settle_frames` to 0 to not wait at all.
+    pub async fn switch_mode<DELAY>(
+        &mut self,
+        delay: &mut DELAY,
+        target_mode: PowerMode,
+    ) -> Result<(), Error<SPI::Error, DC::Error>>
+    where
+        DELAY: DelayNs,
+    {
+        if target_mode == self.buffer.power_mode {
+            return Ok(());
+        }
+        match target_mode {
+            PowerMode::Hpm => self.write_command(Instruction::HPM, &[]).await?,
+            PowerMode::Lpm => self.write_command(Instruction::LPM, &[]).await?,
+        }
+        self.buffer.power_mode = target_mode;
+        let frame_ms = self.buffer.fps.frame_interval_ms(target_mode);
+        let settle_frames = self.buffer.timings.settle_frames;
+        delay.delay_ms(settle_frames as u32 * frame_ms as u32).await;
+        Ok(())
+    }
+
+    /// See [`crate::ST7306::refresh_once()`].
+    pub async fn refresh_once<DELAY>(
+        &mut self,
+        delay: &mut DELAY,
+    ) -> Result<(), Error<SPI::Error, DC::Error>>
+    where
+        DELAY: DelayNs,
+    {
+        let previous = self.buffer.power_mode;
+        self.switch_mode(delay, PowerMode::Hpm).await?;
+        self.flush().await?;
+        let frame_ms = self.buffer.fps.frame_interval_ms(PowerMode::Hpm);
+        let settle_frames = self.buffer.timings.settle_frames;
+        delay.delay_ms(settle_frames as u32 * frame_ms as u32).await;
+        if previous == PowerMode::Lpm {
+            self.write_command(Instruction::LPM, &[]).await?;
+            self.buffer.power_mode = PowerMode::Lpm;
+        }
+        Ok(())
+    }
+
+    /// Write a list of commands, in order
+    async fn write_commands(
+        &mut self,
+        commands: &CommandList,
+    ) -> Result<(), Error<SPI::Error, DC::Error>> {
+        for (command, params) in commands.iter() {
+            self.write_command(command, params).await?;
+        }
+        Ok(())
+    }
+
+    /// Write a command with optional parameters
+    pub async fn write_command(
+        &mut self,
+        command: Instruction,
+        params: &[u8],
+    ) -> Result<(), Error<SPI::Error, DC::Error>> {
+        self.dc.set_low().map_err(Error::Pin)?;
+        self.spi.write(&[command as u8]).await.map_err(Error::Spi)?;
+        if !params.is_empty() {
+            self.dc.set_high().map_err(Error::Pin)?;
+            self.spi.write(params).await.map_err(Error::Spi)?;
+        }
+        Ok(())
+    }
+
+    /// Set the RAM address window to the whole display
+    async fn write_full_window(&mut self) -> Result<(), Error<SPI::Error, DC::Error>> {
+        let window = self.buffer.addr_window;
+        self.write_columns(window.col_start, window.col_end).await?;
+        self.write_rows(window.row_start, window.row_end).await?;
+        self.buffer.ram_window = Some(window);
+        Ok(())
+    }
+
+    /// Send CASET with controller column addresses
+    async fn write_columns(
+        &mut self,
+        start: u16,
+        end: u16,
+    ) -> Result<(), Error<SPI::Error, DC::Error>> {
+        self.write_command(Instruction::CASET, &[start as u8, end as u8])
+            .await?;
+        self.buffer.columns_written(start, end);
+        Ok(())
+    }
+
+    /// Send RASET with controller row addresses
+    async fn write_rows(
+        &mut self,
+        start: u16,
+        end: u16,
+    ) -> Result<(), Error<SPI::Error, DC::Error>> {
+        self.write_command(Instruction::RASET, &[start as u8, end as u8])
+            .await?;
+        self.buffer.rows_written(start, end);
+        Ok(())
+    }
+
+    /// Hard reset the controller by toggling the reset pin
+    ///
+    /// See [`crate::ST7306::hard_reset()`].
+    pub async fn hard_reset<DELAY>(
+        &mut self,
+        delay: &mut DELAY,
+    ) -> Result<(), Error<SPI::Error, DC::Error>>
+    where
+        DELAY: DelayNs,
+    {
+        self.rst.set_high().map_err(Error::Pin)?;
+        delay.delay_ms(self.buffer.timings.reset_ms as u32).await;
+
+        self.rst.set_low().map_err(Error::Pin)?;
+        delay.delay_ms(self.buffer.timings.reset_ms as u32).await;
+
+        self.rst.set_high().map_err(Error::Pin)?;
+        self.buffer.reset_state();
+        Ok(())
+    }
+}
@@ -1,5 +1,6 @@
 /// ST7735 instructions.
 #[derive(Debug, Clone, Copy)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
 pub enum Instruction {
     NOP = 0x00,
     /// Software Reset
@@ -30,13 +31,15 @@ pub enum Instruction {
     RASET = 0x2B,
     // Memory Write
     RAMWR = 0x2C,
+    /// Partial Area
+    PTLAR = 0x30,
     /// Tearing Effect Line Offf
     TEOFF = 0x34,
     /// Tearing Effect Line On
     TEON = 0x35,
     /// Memory Data Access Control
     MADCTL = 0x36,
-    // Vertial Scroll Start Address of RAM
+    /// Vertical Scroll Start Address of RAM
     VSCSAD = 0x37,
     /// High Power Mode ON
     HPM = 0x38,
@@ -1,6 +1,4 @@
 #![no_std]
-#![allow(clippy::result_unit_err)]
-// TODO: Make the config nicer, instead of ST7306::new with tons of arguments
 #![allow(clippy::too_many_arguments)]
 
 //! This crate provides an ST7306 driver to connect to TFT displays.
@@ -10,18 +8,42 @@
 //!
 //! With the "graphics" feature enabled (which is the default) support for
 //! the embedded-traits crate is built-in.
+//! Without it, draw with [`ST7306::set_pixel_on()`], [`ST7306::fill_rect()`]
+//! and [`ST7306::clear_buffer()`] and then call [`ST7306::flush()`].
 //!
-//! Currently the crate assumes a mono color display.
+//! The display is mono by default, [`ColorMode::Gray4`] enables 4 gray levels.
+//!
+//! Images are drawn with embedded-graphics' [`Image`] from raw big endian
+//! RGB565 data, or with [`ST7306::draw_image()`], which does the same:
+//!
+//! ```ignore
+//! use embedded_graphics::{image::{Image, ImageRaw}, pixelcolor::Rgb565, prelude::*};
+//!
+//! let raw: ImageRaw<Rgb565> = ImageRaw::new(include_bytes!("ferris.raw"), 64);
+//! Image::new(&raw, Point::new(10, 20)).draw(&mut display)?;
+//! display.flush()?;
+//! ```
 
+#[cfg(feature = "async")]
+pub mod asynch;
 pub mod instruction;
+#[cfg(feature = "graphics")]
+pub mod mono;
 
 use crate::instruction::Instruction;
 
+use core::marker::PhantomData;
+use core::mem::MaybeUninit;
+use core::ops::RangeInclusive;
+use core::ptr::addr_of_mut;
+
 use embedded_hal::blocking::delay::DelayMs;
 use embedded_hal::blocking::spi;
-use embedded_hal::digital::v2::OutputPin;
+use embedded_hal::digital::v2::{InputPin, OutputPin};
 
 #[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum PowerMode {
     /// Low Power Mode
     Lpm,
@@ -29,44 +51,285 @@ pub enum PowerMode {
     Hpm,
 }
 
-const COL_MAX: u16 = 59;
-const ROW_MAX: u16 = 199;
+const COL_MAX: u16 = PanelGeometry::ST7306.columns - 1;
+const ROW_MAX: u16 = PanelGeometry::ST7306.rows - 1;
 
-const PX_PER_COL: u16 = 12;
-const PX_PER_ROW: u16 = 2;
+/// Most gate lines the controller can drive, as configured with GATESET
+const GATE_LINES_MAX: u16 = 0x64;
+/// Pixel lines per GATESET line, so 100 lines cover 400px
+const PX_PER_GATE_LINE: u16 = 4;
 
-/// Columns go from 0 to 59 (12px per col, so 720px)
-/// Rows go from 0 to 200 (2px per row, so 400px)
-/// But if the display isn't 720x400, we need to set the actual range.
-struct AddrWindow {
-    col_start: u16,
-    col_end: u16,
-    row_start: u16,
-    row_end: u16,
+const PX_PER_COL: u16 = PanelGeometry::ST7306.px_per_col;
+const PX_PER_ROW: u16 = PanelGeometry::ST7306.px_per_row;
+/// In 4-level grayscale mode the cell's 24 bits hold 6x2 pixels
+const GRAY_PX_PER_COL: u16 = 6;
+
+/// Brightness below which a pixel is drawn black in mono mode
+const BLACK_THRESHOLD: u8 = 128;
+
+/// Maximum SPI clock frequency for writing to the controller
+///
+/// Limited by the minimum serial clock cycle for writes (tSCYCW) of the
+/// ST7306. A faster clock corrupts commands and data, which usually shows up
+/// as a garbled or blank screen.
+pub const MAX_SPI_HZ: u32 = 15_000_000;
+
+// Memory Data Access Control. Default, nothing inverted
+//                           0      = MY (Page Address Order) Flips picture upside down
+//                            1     = MX (Column Address Order)
+//                             0    = MV (Page/Column Order)
+//                               1  = DO (Data Order)
+//                                0 = GS (Gate Scan Order)
+//                           010010
+// Make sure pixel 0,0 is in the top left
+const MADCTL_DEFAULT: u8 = 0b01001000;
+/// Data Order bit of MADCTL
+const MADCTL_DO: u8 = 0b00001000;
+
+/// Most bytes a read command can receive, see `read_command()`
+const READ_MAX: usize = 16;
+/// Parameter of NVMRDEN and NVMPROM to enable reading or programming
+const NVM_ENABLE: u8 = 0x01;
+/// Parameter of NVMRDEN and NVMPROM to disable reading or programming
+const NVM_DISABLE: u8 = 0x00;
+/// Parameter of EXTBCTRL to enable the extended command set
+const EXTB_ENABLE: u8 = 0x01;
+/// Parameter of EXTBCTRL to disable the extended command set
+const EXTB_DISABLE: u8 = 0x00;
+/// Number of parameters of VSIKCTRL, see [`ST7306::set_gamma_voltage()`]
+pub const GAMMA_VOLTAGE_LEN: usize = 4;
+/// How long to wait for the NVM to be programmed
+const NVM_PROGRAM_MS: u8 = 100;
+
+/// Bytes to set the address window, CASET and RASET with two parameters each
+const WINDOW_BYTES: usize = 6;
+
+/// Default VSHP and VSHN codes, 4.02V and -3.28V
+const VSHP_DEFAULT: u8 = 0x0B;
+const VSHN_DEFAULT: u8 = 0x27;
+/// Highest code of the gate voltages, see [`VoltageConfig`]
+const GATE_VOLTAGE_MAX: u8 = 0x0F;
+/// Highest code of the source voltages, see [`VoltageConfig`]
+const SOURCE_VOLTAGE_MAX: u8 = 0x3F;
+/// How far set_brightness and set_contrast move the source voltages from
+/// the configured ones
+const BRIGHTNESS_RANGE: i16 = 6;
+/// Brightness and contrast level that keeps the configured voltages
+const LEVEL_DEFAULT: u8 = 128;
+
+/// Default number of idle calls before auto_power drops back to LPM
+const AUTO_POWER_TIMEOUT: u16 = 10;
+
+/// Frames at the new rate to wait for after switching the power mode
+const SETTLE_FRAMES: u16 = 2;
+
+/// Widest ASCII dump in characters, wider framebuffers are scaled down
+const ASCII_MAX_WIDTH: u16 = 80;
+
+/// How many identical cells are sent per SPI write when RLE flushing
+const RLE_CHUNK_CELLS: usize = 16;
+
+/// How many cells write_ram_iter collects per SPI write
+const ITER_CHUNK_CELLS: usize = 16;
+
+/// Most parameters of a command in a `CommandList`, those of GTUPEQH
+const PARAMS_MAX: usize = 10;
+/// Most commands in a `CommandList`
+const COMMANDS_MAX: usize = 16;
+
+/// Layout of the controller's display RAM
+///
+/// The RAM is addressed in cells of 12x2 pixels. Use
+/// [`PanelGeometry::ST7306`] to check a display size and offset before
+/// creating the driver.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct PanelGeometry {
+    /// Number of RAM columns
+    pub columns: u16,
+    /// Number of RAM rows
+    pub rows: u16,
+    /// Pixels per column, in mono mode
+    pub px_per_col: u16,
+    /// Pixels per row
+    pub px_per_row: u16,
+}
+
+impl PanelGeometry {
+    /// The ST7306: 60 columns of 12px and 200 rows of 2px, so 720x400px
+    pub const ST7306: PanelGeometry = PanelGeometry {
+        columns: 60,
+        rows: 200,
+        px_per_col: 12,
+        px_per_row: 2,
+    };
+
+    /// Check a display size in pixels and offset in columns and rows
+    ///
+    /// Returns the RAM window that the display covers. The same checks are
+    /// done by [`ST7306Builder::build()`] and [`ST7306::new()`].
+    pub fn validate(
+        &self,
+        width: u16,
+        height: u16,
+        col_start: u16,
+        row_start: u16,
+    ) -> Result<AddrWindow, GeometryError> {
+        if width == 0 || height == 0 {
+            return Err(GeometryError::EmptyDisplay);
+        }
+        if !width.is_multiple_of(self.px_per_col) || !height.is_multiple_of(self.px_per_row) {
+            return Err(GeometryError::UnalignedSize);
+        }
+        let window = self.window(width, height, col_start, row_start);
+        if window.col_end >= self.columns || window.row_end >= self.rows {
+            return Err(GeometryError::WindowOutOfRange);
+        }
+        Ok(window)
+    }
+
+    /// RAM window of a display made of whole cells
+    fn window(&self, width: u16, height: u16, col_start: u16, row_start: u16) -> AddrWindow {
+        // 0 indexed
+        let cols = width / self.px_per_col;
+        let rows = height / self.px_per_row;
+        AddrWindow {
+            col_start,
+            col_end: col_start.saturating_add(cols).saturating_sub(1),
+            row_start,
+            row_end: row_start.saturating_add(rows).saturating_sub(1),
+        }
+    }
+}
+
+/// Display size or offset that doesn't fit the panel, see
+/// [`PanelGeometry::validate()`]
+///
+/// The builder reports them as the [`ConfigError`] of the same name.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum GeometryError {
+    /// Width or height is zero
+    EmptyDisplay,
+    /// Width is not a multiple of 12 or height not a multiple of 2
+    UnalignedSize,
+    /// The address window goes beyond the controller's last column or row
+    WindowOutOfRange,
+}
+
+impl From<GeometryError> for ConfigError {
+    fn from(err: GeometryError) -> Self {
+        match err {
+            GeometryError::EmptyDisplay => ConfigError::EmptyDisplay,
+            GeometryError::UnalignedSize => ConfigError::UnalignedSize,
+            GeometryError::WindowOutOfRange => ConfigError::WindowOutOfRange,
+        }
+    }
+}
+
+/// Window of RAM cells, inclusive, see [`PanelGeometry::validate()`]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct AddrWindow {
+    /// First column
+    pub col_start: u16,
+    /// Last column
+    pub col_end: u16,
+    /// First row
+    pub row_start: u16,
+    /// Last row
+    pub row_end: u16,
 }
 
 #[repr(u8)]
 #[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 /// The framerate when in high power mode
 pub enum HpmFps {
+    /// 16Hz
     Sixteen = 0b00000000,
+    /// 32Hz
     ThirtyTwo = 0b00010000,
 }
 
+impl HpmFps {
+    /// Every rate, from the slowest to the fastest
+    const ALL: [HpmFps; 2] = [HpmFps::Sixteen, HpmFps::ThirtyTwo];
+
+    /// Frames per second
+    pub fn hz(&self) -> f32 {
+        match self {
+            HpmFps::Sixteen => 16.0,
+            HpmFps::ThirtyTwo => 32.0,
+        }
+    }
+
+    /// Slowest rate that refreshes at least `hz` times per second
+    ///
+    /// Slower rates use less power. Returns `None` if even the fastest is
+    /// too slow.
+    pub fn for_min_hz(hz: f32) -> Option<Self> {
+        Self::ALL.into_iter().find(|fps| fps.hz() >= hz)
+    }
+}
+
 #[repr(u8)]
 #[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 /// The framerate when in low power mode
 pub enum LpmFps {
+    /// 0.25Hz
     Quarter = 0b000,
+    /// 0.5Hz
     Half = 0b001,
+    /// 1Hz
     One = 0b010,
+    /// 2Hz
     Two = 0b011,
+    /// 4Hz
     Four = 0b100,
+    /// 8Hz
     Eight = 0b101,
 }
 
+impl LpmFps {
+    /// Every rate, from the slowest to the fastest
+    const ALL: [LpmFps; 6] = [
+        LpmFps::Quarter,
+        LpmFps::Half,
+        LpmFps::One,
+        LpmFps::Two,
+        LpmFps::Four,
+        LpmFps::Eight,
+    ];
+
+    /// Frames per second
+    pub fn hz(&self) -> f32 {
+        match self {
+            LpmFps::Quarter => 0.25,
+            LpmFps::Half => 0.5,
+            LpmFps::One => 1.0,
+            LpmFps::Two => 2.0,
+            LpmFps::Four => 4.0,
+            LpmFps::Eight => 8.0,
+        }
+    }
+
+    /// Slowest rate that refreshes at least `hz` times per second
+    ///
+    /// Slower rates use less power. Returns `None` if even the fastest is
+    /// too slow.
+    pub fn for_min_hz(hz: f32) -> Option<Self> {
+        Self::ALL.into_iter().find(|fps| fps.hz() >= hz)
+    }
+}
+
 /// Configure the display's frame-rate in high and low-power mode
 #[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct FpsConfig {
     pub hpm: HpmFps,
     pub lpm: LpmFps,
@@ -77,7 +340,31 @@ impl FpsConfig {
     pub fn as_u8(&self) -> u8 {
         (self.hpm as u8) + (self.lpm as u8)
     }
+    /// Duration of one frame in milliseconds, rounded up
+    pub fn frame_interval_ms(&self, mode: PowerMode) -> u16 {
+        match mode {
+            PowerMode::Hpm => match self.hpm {
+                HpmFps::Sixteen => 63,
+                HpmFps::ThirtyTwo => 32,
+            },
+            PowerMode::Lpm => match self.lpm {
+                LpmFps::Quarter => 4000,
+                LpmFps::Half => 2000,
+                LpmFps::One => 1000,
+                LpmFps::Two => 500,
+                LpmFps::Four => 250,
+                LpmFps::Eight => 125,
+            },
+        }
+    }
+    /// Parse an FRCTRL byte, the inverse of [`Self::as_u8()`]
+    ///
+    /// Returns `None` for reserved LPM values and if any bit other than the
+    /// HPM and LPM fields is set.
     pub fn from_u8(byte: u8) -> Option<Self> {
+        if byte & !0b00010111 != 0 {
+            return None;
+        }
         let lpm = match byte & 0b111 {
             0b000 => LpmFps::Quarter,
             0b001 => LpmFps::Half,
@@ -96,698 +383,7320 @@ impl FpsConfig {
     }
 }
 
-/// ST7306 driver to connect to TFT displays.
-pub struct ST7306<SPI, DC, CS, RST, const COLS: usize, const ROWS: usize>
-where
-    SPI: spi::Write<u8>,
-    DC: OutputPin,
-    CS: OutputPin,
-    RST: OutputPin,
-{
-    /// SPI
-    pub spi: SPI,
+/// Polarity inversion scheme of the panel, bits 6-5 of PNLSET
+///
+/// The panel has to be driven with alternating polarity. How the polarity
+/// alternates between neighbouring pixels trades shimmer against power: the
+/// finer the pattern, the less visible the flicker, but the more often the
+/// source lines switch. The reference code uses [`Self::OneDot`], the
+/// codes of the others should be checked against the datasheet of your
+/// module.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Inversion {
+    /// Whole columns alternate, uses the least power but shimmers the most
+    Column = 0b00,
+    /// Every pixel alternates with its neighbours, the least shimmer
+    OneDot = 0b01,
+    /// Pairs of pixels alternate, in between the two
+    TwoDot = 0b10,
+    /// The whole frame alternates at once, can flicker visibly
+    Frame = 0b11,
+}
 
-    /// Data/command pin.
-    pub dc: DC,
+/// Analog tuning of the panel
+///
+/// The defaults are the values of the reference init code. They are written
+/// to the controller by [`ST7306::init()`].
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct TuningConfig {
+    /// Source EQ (SOUEQ)
+    ///
+    /// Controls the equalization of the source lines before new data is
+    /// driven, which determines how sharply pixels transition. Panels with a
+    /// different source line load might need another value to avoid smearing
+    /// or ghosting.
+    pub source_eq: u8,
 
-    /// Chip select pin
-    pub cs: CS,
+    /// Update period gate EQ in high power mode (GTUPEQH)
+    ///
+    /// Timing of the gate line equalization while the panel is updated in
+    /// high power mode. Wrong values show up as flicker or uneven rows.
+    pub hpm_eq: [u8; 10],
 
-    /// Reset pin.
-    pub rst: RST,
+    /// Update period gate EQ in low power mode (GTUPEQL)
+    ///
+    /// Same as [`Self::hpm_eq`], but for low power mode.
+    pub lpm_eq: [u8; 8],
 
-    /// Whether the colours are inverted (true) or not (false)
-    inverted: bool,
+    /// Inversion scheme, written as bits 6-5 of the panel setting (PNLSET)
+    ///
+    /// Panels that shimmer with the default might look better with another
+    /// one, see [`Inversion`].
+    pub inversion: Inversion,
 
-    /// Internal framebuffer to keep pixels until flushing
-    framebuffer: [[[u8; 3]; COLS]; ROWS],
+    /// Frame interval, written as bits 3-2 of the panel setting (PNLSET)
+    ///
+    /// Changes the interval between frames the panel is driven. Panels that
+    /// flicker at the default might look better with a different value.
+    /// Must be between 0 and 3.
+    pub frame_interval: u8,
 
-    /// Auto power down
-    autopowerdown: bool,
+    /// Oscillator setting (OSCSET)
+    ///
+    /// Sets the internal oscillator that the frame rates are derived from.
+    /// The first byte is for high power mode. The default is the reference
+    /// code's value, the datasheet suggests `[0x26, 0xE9]`. Panels that
+    /// flicker at the configured frame rate might need a different divider.
+    pub oscillator: [u8; 2],
+}
 
-    /// Enable tearing pin
-    te_enable: bool,
+impl Default for TuningConfig {
+    fn default() -> Self {
+        Self {
+            source_eq: 0x13,
+            hpm_eq: [0xE5, 0xF6, 0x05, 0x46, 0x77, 0x77, 0x77, 0x77, 0x76, 0x45],
+            lpm_eq: [0x05, 0x46, 0x77, 0x77, 0x77, 0x77, 0x76, 0x45],
+            inversion: Inversion::OneDot,
+            frame_interval: 0b10,
+            oscillator: [0xA6, 0xE9],
+        }
+    }
+}
 
-    /// Frame rate configuration
-    fps: FpsConfig,
+impl TuningConfig {
+    /// Panel setting byte, as accepted by the PNLSET command
+    ///
+    /// Sets the inversion and the frame interval, plus the one-line
+    /// interface. The default is 0x29.
+    pub fn panel_setting(&self) -> u8 {
+        ((self.inversion as u8) << 5) | ((self.frame_interval & 0b11) << 2) | 0b01
+    }
+}
 
-    /// Display width in pixels
-    width: u16,
+/// Gate and source voltages of the panel
+///
+/// Panels of different batches might need slightly different voltages to
+/// avoid ghosting. The defaults are the values of the reference init code.
+/// They are written to the controller by [`ST7306::init()`].
+///
+/// The source voltages have one value for each of the four gray levels.
+/// They're 6 bit codes in steps of 0.02V, so at most `0x3F`. The gate
+/// voltages are at most `0x0F`. Larger codes are rejected by
+/// [`ST7306::validate_config()`], they would set bits the controller
+/// reserves.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct VoltageConfig {
+    /// Gate voltage control (GCTRL), VGH and VGL
+    ///
+    /// Default: VGH 12V, VGL -6V
+    pub gate: [u8; 2],
 
-    /// Display height in pixels
-    height: u16,
-    addr_window: AddrWindow,
+    /// Positive source high voltage (VSHPCTRL)
+    ///
+    /// Default: 4.02V
+    pub vshp: [u8; 4],
 
-    /// Whether currently sleeping
-    sleeping: bool,
+    /// Positive source low voltage (VSLPCTRL)
+    ///
+    /// Default: 0.8V
+    pub vslp: [u8; 4],
 
-    /// Current power mode
-    power_mode: PowerMode,
+    /// Negative source high voltage (VSHNCTRL)
+    ///
+    /// Default: -3.28V
+    pub vshn: [u8; 4],
 
-    /// Whether the display is currently on
-    display_on: bool,
+    /// Negative source low voltage (VSLNCTRL)
+    ///
+    /// Default: -0.06V
+    pub vsln: [u8; 4],
 }
 
-#[derive(Clone, Copy)]
-pub enum Orientation {
-    Portrait = 0x00,
-    Landscape = 0x60,
-    PortraitSwapped = 0xC0,
-    LandscapeSwapped = 0xA0,
+impl Default for VoltageConfig {
+    fn default() -> Self {
+        Self {
+            gate: [0x08, 0x02],
+            vshp: [VSHP_DEFAULT; 4],
+            vslp: [0x23; 4],
+            vshn: [VSHN_DEFAULT; 4],
+            vsln: [0x35; 4],
+        }
+    }
 }
 
-impl<SPI, DC, CS, RST, const COLS: usize, const ROWS: usize> ST7306<SPI, DC, CS, RST, COLS, ROWS>
+impl VoltageConfig {
+    /// Whether all codes are within the ranges the controller accepts
+    fn in_range(&self) -> bool {
+        let source = [self.vshp, self.vslp, self.vshn, self.vsln];
+        self.gate.iter().all(|code| *code <= GATE_VOLTAGE_MAX)
+            && source
+                .as_flattened()
+                .iter()
+                .all(|code| *code <= SOURCE_VOLTAGE_MAX)
+    }
+}
+
+/// What the controller loads from the NVM (NVMLOADCTRL)
+///
+/// By default nothing is enabled, like in the reference code, and the
+/// voltages written by init are used. The reference code also has a
+/// commented out variant that enables everything, `[0x17, 0x02]`. To see
+/// what's stored in the NVM, read it with [`ST7306::nvm_read()`].
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct NvmLoadCtrl {
+    /// Load the source voltage trim (VS_EN)
+    pub vs_en: bool,
+    /// Load the ID registers (ID_EN)
+    pub id_en: bool,
+    /// Reload periodically, triggered by a timer
+    pub load_by_timer: bool,
+    /// Reload on sleep out
+    pub load_by_slpout: bool,
+}
+
+impl NvmLoadCtrl {
+    /// Parameters of the NVMLOADCTRL command
+    pub fn as_bytes(&self) -> [u8; 2] {
+        [
+            0b10001 | (self.vs_en as u8) << 2 | (self.id_en as u8) << 1,
+            (self.load_by_slpout as u8) << 1 | self.load_by_timer as u8,
+        ]
+    }
+}
+
+/// Delays of the reset, init and power mode sequences
+///
+/// All in milliseconds, except for the settle time after switching the power
+/// mode, which is in frames at the new frame rate. The defaults are on the
+/// safe side. Shorter delays speed up booting, but check that the panel
+/// still comes up reliably, some need even longer ones.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Timings {
+    /// Each of the high and low phases of a hard reset
+    pub reset_ms: u16,
+    /// After SWRESET
+    pub soft_reset_ms: u16,
+    /// After SLPOUT in [`ST7306::power_on()`], before the rest of init
+    pub power_on_ms: u16,
+    /// After SLPOUT in [`ST7306::sleep_out()`]
+    pub sleep_out_ms: u16,
+    /// After SLPIN
+    pub sleep_in_ms: u16,
+    /// Extra wait after leaving low power mode to go to sleep
+    pub lpm_exit_ms: u16,
+    /// Frames at the new frame rate after switching the power mode
+    pub settle_frames: u16,
+}
+
+impl Default for Timings {
+    fn default() -> Self {
+        Self {
+            reset_ms: 10,
+            soft_reset_ms: 200,
+            power_on_ms: 255,
+            sleep_out_ms: 100,
+            sleep_in_ms: 100,
+            lpm_exit_ms: 255,
+            settle_frames: SETTLE_FRAMES,
+        }
+    }
+}
+
+/// Parameter of AUTOPWRCTRL to enable or disable auto power down
+fn auto_power_down_param(enable: bool) -> u8 {
+    if enable {
+        0xFF
+    } else {
+        0x7F
+    }
+}
+
+/// Map a level from 0 to 255 to a voltage code offset, see
+/// [`ST7306::set_brightness()`]
+fn level_to_delta(level: u8) -> i16 {
+    level as i16 * (2 * BRIGHTNESS_RANGE) / 255 - BRIGHTNESS_RANGE
+}
+
+/// Move voltage codes by `delta` steps of 0.02V, within the valid codes
+fn shift_voltage(codes: [u8; 4], delta: i16) -> [u8; 4] {
+    codes.map(|code| (code as i16 + delta).clamp(0, SOURCE_VOLTAGE_MAX as i16) as u8)
+}
+
+/// FNV-1a hash of a framebuffer row, see [`ST7306::flush_rows()`]
+#[cfg(feature = "row-hash")]
+fn row_hash(bytes: &[u8]) -> u32 {
+    bytes.iter().fold(0x811c9dc5, |hash, byte| {
+        (hash ^ *byte as u32).wrapping_mul(0x01000193)
+    })
+}
+
+/// Write data in chunks of at most `max_transfer` bytes
+fn write_chunked<SPI>(spi: &mut SPI, data: &[u8], max_transfer: usize) -> Result<(), SPI::Error>
 where
     SPI: spi::Write<u8>,
-    DC: OutputPin,
-    CS: OutputPin,
-    RST: OutputPin,
 {
-    /// Creates a new driver instance that uses hardware SPI.
-    pub fn new(
-        spi: SPI,
-        dc: DC,
-        cs: CS,
-        rst: RST,
-        inverted: bool,
-        autopowerdown: bool,
-        te_enable: bool,
-        fps: FpsConfig,
-        width: u16,
-        height: u16,
-        col_start: u16,
-        row_start: u16,
-    ) -> Self {
-        // TODO: This might be incorrect, if the pixels don't fit exactly into cols and rows
-        // 0 indexed
-        let col_end = col_start + (width / PX_PER_COL) - 1;
-        let row_end = row_start + (height / PX_PER_ROW) - 1;
-        assert!(col_end <= COL_MAX);
-        assert!(row_end <= ROW_MAX);
+    for chunk in data.chunks(max_transfer) {
+        spi.write(chunk)?;
+    }
+    Ok(())
+}
 
-        let addr_window = AddrWindow {
-            col_start,
-            col_end,
-            row_start,
-            row_end,
-        };
-        ST7306 {
-            spi,
-            dc,
-            cs,
-            rst,
-            inverted,
-            framebuffer: [[[0; 3]; COLS]; ROWS],
-            fps,
-            autopowerdown,
-            te_enable,
-            width,
-            height,
-            sleeping: true,
-            power_mode: PowerMode::Hpm,
-            display_on: false,
-            addr_window,
+/// Split cells into runs of identical cells, see
+/// [`ST7306::set_rle_flush()`]
+///
+/// Yields each cell with how often it repeats, at most
+/// [`RLE_CHUNK_CELLS`] times, so a long run comes as several chunks.
+fn rle_runs(cells: &[[u8; 3]]) -> impl Iterator<Item = ([u8; 3], usize)> + '_ {
+    let mut i = 0;
+    core::iter::from_fn(move || {
+        let cell = *cells.get(i)?;
+        let run = cells[i..]
+            .iter()
+            .take(RLE_CHUNK_CELLS)
+            .take_while(|c| **c == cell)
+            .count();
+        i += run;
+        Some((cell, run))
+    })
+}
+
+/// A sequence of commands with their parameters
+///
+/// The blocking and the async driver build their command sequences with
+/// the same functions, then each writes them to its bus. That way the two
+/// send exactly the same bytes.
+struct CommandList {
+    commands: [(Instruction, [u8; PARAMS_MAX], usize); COMMANDS_MAX],
+    len: usize,
+}
+
+impl CommandList {
+    fn new() -> Self {
+        CommandList {
+            commands: [(Instruction::NOP, [0; PARAMS_MAX], 0); COMMANDS_MAX],
+            len: 0,
         }
     }
 
-    /// Draw individual pixels
-    ///
-    /// Since the display controller doesn't have a command to send individual
-    /// pixels, we draw it to a framebuffer and then optionally flush all of
-    /// that to the contoller.
-    pub fn draw_pixels<I>(&mut self, pixels: I, flush: bool) -> Result<(), ()>
-    where
-        I: IntoIterator<Item = Pixel<Rgb565>>,
-    {
-        for Pixel(coord, color) in pixels.into_iter() {
-            // Only draw pixels that would be on screen
-            if coord.x >= 0
-                && coord.y >= 0
-                && coord.x < self.width as i32
-                && coord.y < self.height as i32
-            {
-                self.set_pixel(
-                    coord.x as u16,
-                    coord.y as u16,
-                    RawU16::from(color).into_inner() as u8,
-                )?;
-            }
-        }
-        if flush {
-            self.flush()?;
-        }
-        Ok(())
+    /// Append a command, panics if there's no room for it
+    fn push(&mut self, instruction: Instruction, params: &[u8]) {
+        let (command, buf, len) = &mut self.commands[self.len];
+        *command = instruction;
+        buf[..params.len()].copy_from_slice(params);
+        *len = params.len();
+        self.len += 1;
     }
 
-    /// Flush the entire framebuffer to the screen
-    ///
-    /// TODO: Support partial screen updates
-    ///       Need to keep track of which cols and rows have changed.
-    pub fn flush(&mut self) -> Result<(), ()> {
-        // TODO: Only need to set address window when doing partial updates
-        //self.write_command(
-        //    Instruction::CASET,
-        //    &[
-        //        self.addr_window.col_start as u8,
-        //        self.addr_window.col_end as u8,
-        //    ],
-        //)?;
-        //// Rows 0-199 (G1-G402). 200 rows, one for 2 pixels => 400px
-        //self.write_command(
-        //    Instruction::RASET,
-        //    &[
-        //        self.addr_window.row_start as u8,
-        //        self.addr_window.row_end as u8,
-        //    ],
-        //)?;
+    /// The commands and their parameters, in order
+    fn iter(&self) -> impl Iterator<Item = (Instruction, &[u8])> {
+        self.commands[..self.len]
+            .iter()
+            .map(|(command, params, len)| (*command, &params[..*len]))
+    }
+}
 
-        self.write_command(Instruction::RAMWR, &[])?;
-        self.start_data()?;
+/// What a flush has to send, see `ST7306::flush_plan()`
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum FlushPlan {
+    /// Nothing changed
+    Skip,
+    /// Only these framebuffer cells, inclusive
+    Cells(AddrWindow),
+    /// The whole framebuffer
+    Full,
+}
 
-        for row in 0..ROWS {
-            for col in 0..COLS {
-                self.write_ram(&[(
-                    self.framebuffer[row][col][0],
-                    self.framebuffer[row][col][1],
-                    self.framebuffer[row][col][2],
-                )])?;
-            }
-        }
-        Ok(())
-    }
-
-    // TODO: Can implement
-    //pub fn fill_contiguous_single_color(
-    //    &mut self,
-    //    area: &Rectangle,
-    //    color: Rgb565,
-    //) -> Result<(), ()> {
-    //    // Clamp area to drawable part of the display target
-    //    let drawable_area = area.intersection(&Rectangle::new(Point::zero(), self.size()));
-    //    let brightness = col_to_bright(color);
-    //    let colors =
-    //        core::iter::repeat(brightness).take((area.size.width * area.size.height) as usize);
-    //    //let colors = area.points()
-    //    //            .filter(|pos| drawable_area.contains(*pos))
-    //    //            .map(|_pos| brightness);
-
-    //    if drawable_area.size != Size::zero() {
-    //        let ex = (drawable_area.top_left.x + (drawable_area.size.width - 1) as i32) as u16;
-    //        let ey = (drawable_area.top_left.y + (drawable_area.size.height - 1) as i32) as u16;
-    //        self.set_pixels_buffered_u8(
-    //            drawable_area.top_left.x as u16,
-    //            drawable_area.top_left.y as u16,
-    //            ex,
-    //            ey,
-    //            colors,
-    //        )?;
-    //    }
-
-    //    Ok(())
-    //}
+/// Bytes of a partial flush of `cols` x `rows` cells
+///
+/// RAMWR and the data, plus setting and restoring the window.
+fn partial_flush_bytes(cols: usize, rows: usize) -> usize {
+    2 * WINDOW_BYTES + 1 + cols * rows * 3
+}
 
-    /// Runs commands to initialize the display.
-    pub fn init<DELAY>(&mut self, delay: &mut DELAY) -> Result<(), ()>
-    where
-        DELAY: DelayMs<u8>,
-    {
-        // First do a hard reset because the controller might be in a bad state
-        // if the voltage was unstable in the beginning.
-        self.hard_reset(delay)?;
-        self.write_command(Instruction::SWRESET, &[])?;
-        delay.delay_ms(200);
-
-        // 0x17 = 10111 VS_EN=1, ID_EN=1 (both off would be 0b10001)
-        // 0x02 = 00010 V  NVM Load by timer=0, load by slpout=1 (both off would be 0b0)
-        //self.write_command(Instruction::NVMLOADCTRL, &[0x17, 0x02])?;
-        self.write_command(Instruction::NVMLOADCTRL, &[0b10001, 0])?;
-        self.write_command(Instruction::BSTEN, &[0x01])?;
-
-        // Gate Voltage Control. VGH: 12V, VGL: -6V
-        self.write_command(Instruction::GCTRL, &[0x08, 0x02])?;
-        // VSHP Control: 4.02V
-        self.write_command(Instruction::VSHPCTRL, &[0x0B, 0x0B, 0x0B, 0x0B])?;
-        // VSLP Control: 0.8V
-        self.write_command(Instruction::VSLPCTRL, &[0x23, 0x23, 0x23, 0x23])?;
-        // VSHN Control: -3.28V
-        self.write_command(Instruction::VSHNCTRL, &[0x27, 0x27, 0x27, 0x27])?;
-        // VSLN Control: -0.06V
-        self.write_command(Instruction::VSLNCTRL, &[0x35, 0x35, 0x35, 0x35])?;
+/// Bytes of a full flush without setting the window, RAMWR and the data
+fn full_flush_bytes<const COLS: usize, const ROWS: usize>() -> usize {
+    1 + COLS * ROWS * 3
+}
 
-        // Datasheet: 0x32, 0x03, 0x1F Reference code: not present
-        //self.write_command(Instruction::GTCON, &[0x32, 0x03, 0x1F])?;
+/// Delay for longer than the 255ms that fit into the u8 of DelayMs
+fn delay_ms<DELAY>(delay: &mut DELAY, ms: u16)
+where
+    DELAY: DelayMs<u8>,
+{
+    let mut remaining = ms;
+    while remaining > 0 {
+        let step = remaining.min(u8::MAX as u16);
+        delay.delay_ms(step as u8);
+        remaining -= step;
+    }
+}
 
-        // Datasheet: 0x26, 0xE9, Reference: 0xA6, 0xE9 (HPM: 32Hz)
-        self.write_command(Instruction::OSCSET, &[0xA6, 0xE9])?;
+/// Find where a pixel is stored in the framebuffer
+///
+/// Returns the row, the column and the byte within the column's three bytes,
+/// as well as the bitmask of the pixel inside of that byte.
+fn pixel_location(x: u16, y: u16) -> (usize, usize, usize, u8) {
+    let row = (y / PX_PER_ROW) as usize;
+    let col = (x / PX_PER_COL) as usize;
 
-        // Frame Rate Control: 32Hz in High Power Mode, 1Hz in Low Power Mode
-        // Examples
-        // 0x12 = 0b10010 (32Hz in HPM, 1Hz in LPM)
-        // 0x15 = 0b10101 (32Hz in HPM, 8Hz in LPM)
-        self.write_command(Instruction::FRCTRL, &[self.fps.as_u8()])?;
+    let (byte, bitmask) = match (x % PX_PER_COL, y % PX_PER_ROW) {
+        (0, 0) => (0, 0x80),
+        (0, 1) => (0, 0x40),
+        (1, 0) => (0, 0x20),
+        (1, 1) => (0, 0x10),
+        (2, 0) => (0, 0x08),
+        (2, 1) => (0, 0x04),
+        (3, 0) => (0, 0x02),
+        (3, 1) => (0, 0x01),
 
-        // HPM EQ Control
-        self.write_command(
-            Instruction::GTUPEQH,
-            &[0xE5, 0xF6, 0x05, 0x46, 0x77, 0x77, 0x77, 0x77, 0x76, 0x45],
-        )?;
-        // LPM EQ Control
-        self.write_command(
-            Instruction::GTUPEQL,
-            &[0x05, 0x46, 0x77, 0x77, 0x77, 0x77, 0x76, 0x45],
-        )?;
-        // Source EQ Enable
-        self.write_command(Instruction::SOUEQ, &[0x13])?;
+        (4, 0) => (1, 0x80),
+        (4, 1) => (1, 0x40),
+        (5, 0) => (1, 0x20),
+        (5, 1) => (1, 0x10),
+        (6, 0) => (1, 0x08),
+        (6, 1) => (1, 0x04),
+        (7, 0) => (1, 0x02),
+        (7, 1) => (1, 0x01),
 
-        // Gate Line Setting:
-        // 0x64 (100) lines. Each line controls 2 pixels. 100*2 = 400px
-        self.write_command(Instruction::GATESET, &[0x64])?;
+        (8, 0) => (2, 0x80),
+        (8, 1) => (2, 0x40),
+        (9, 0) => (2, 0x20),
+        (9, 1) => (2, 0x10),
+        (10, 0) => (2, 0x08),
+        (10, 1) => (2, 0x04),
+        (11, 0) => (2, 0x02),
+        (11, 1) => (2, 0x01),
+        _ => panic!("Impossible to reach"),
+    };
 
-        // Exit sleep mode
-        self.write_command(Instruction::SLPOUT, &[])?;
-        self.sleeping = false;
-        delay.delay_ms(255);
+    (row, col, byte, bitmask)
+}
 
-        // Ultra low power code (undocumented command)
-        self.write_command(Instruction::LOWPOWER, &[0xC1, 0x4A, 0x26])?;
+/// Find where a 2 bit pixel is stored in the framebuffer, in Gray4 mode
+///
+/// Same as [`pixel_location()`], but the bitmask covers both bits of the
+/// pixel. Pixels are packed in the same order as in mono mode.
+fn gray_pixel_location(x: u16, y: u16) -> (usize, usize, usize, u8) {
+    let row = (y / PX_PER_ROW) as usize;
+    let col = (x / GRAY_PX_PER_COL) as usize;
 
-        // Source Voltage Select: VSHP1, VSLP1, VSHN1, VSLN1
-        self.write_command(Instruction::VSHLSEL, &[0x00])?;
-
-        // Memory Data Access Control. Default, nothing inverted
-        //                 0      = MY (Page Address Order) Flips picture upside down
-        //                  1     = MX (Column Address Order)
-        //                   0    = MV (Page/Column Order)
-        //                     1  = DO (Data Order)
-        //                      0 = GS (Gate Scan Order)
-        //                 010010
-        // Make sure pixel 0,0 is in the top left
-        let madctl: u8 = 0b01001000;
-        self.write_command(Instruction::MADCTL, &[madctl])?;
-
-        // Data Format: XDE=1, BPS=1 (3 bytes for 24 bits)
-        self.write_command(Instruction::DTFORM, &[0x11])?;
-
-        // Gamma Mode: Mono
-        self.write_command(Instruction::GAMAMS, &[0x20])?;
-
-        // Panel Setting
-        //  01      = 1-Dot Inversion
-        //  || 10   = Frame Interval
-        //  || ||01 = One-Line Interface
-        //  || ||||
-        // 00101001 = 0x29
-        self.write_command(Instruction::PNLSET, &[0x29])?;
+    let x = x % GRAY_PX_PER_COL;
+    let byte = (x / 2) as usize;
+    let position = (x % 2) * 2 + y % PX_PER_ROW;
+    let bitmask = 0b11000000 >> (position * 2);
 
-        // Column and row settings.
-        // Will be overridden by each pixel write
-        // Columns 18-42 (S217-S516). 25 columns, one for 12 pixels => 300px
-        self.write_command(
-            Instruction::CASET,
-            &[
-                self.addr_window.col_start as u8,
-                self.addr_window.col_end as u8,
-            ],
-        )?;
-        // Rows 0-199 (G1-G402). 200 rows, one for 2 pixels => 400px
-        self.write_command(
-            Instruction::RASET,
-            &[
-                self.addr_window.row_start as u8,
-                self.addr_window.row_end as u8,
-            ],
-        )?;
+    (row, col, byte, bitmask)
+}
 
-        // Enable auto power down
-        if self.autopowerdown {
-            self.write_command(Instruction::AUTOPWRCTRL, &[0xFF])?;
-        } else {
-            self.write_command(Instruction::AUTOPWRCTRL, &[0x7F])?;
-        }
+/// Mirror the pixels inside of a framebuffer cell
+///
+/// `bits` is the number of bits per pixel, 1 in mono and 2 in Gray4 mode.
+/// The pixels are packed column by column, see [`pixel_location()`].
+fn mirror_cell(cell: [u8; 3], bits: u32, mirror_x: bool, mirror_y: bool) -> [u8; 3] {
+    let value = u32::from_be_bytes([0, cell[0], cell[1], cell[2]]);
+    let columns = 24 / (bits * PX_PER_ROW as u32);
+    let rows = PX_PER_ROW as u32;
+    let position = |column: u32, row: u32| 24 - bits * (column * rows + row + 1);
 
-        // Tearing enable on
-        if self.te_enable {
-            // 0x00 means V-blanking only
-            // 0x01 means V and H-blanking
-            self.write_command(Instruction::TEON, &[0x00])?;
-        } else {
-            self.write_command(Instruction::TEOFF, &[])?;
+    let mut mirrored = 0;
+    for column in 0..columns {
+        for row in 0..rows {
+            let pixel = (value >> position(column, row)) & ((1 << bits) - 1);
+            let column = if mirror_x {
+                columns - 1 - column
+            } else {
+                column
+            };
+            let row = if mirror_y { rows - 1 - row } else { row };
+            mirrored |= pixel << position(column, row);
         }
+    }
+    let [_, high, middle, low] = mirrored.to_be_bytes();
+    [high, middle, low]
+}
 
-        // Go into low power mode by default
-        self.write_command(Instruction::LPM, &[])?;
-        self.power_mode = PowerMode::Lpm;
+/// Color mode of the display
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum ColorMode {
+    /// Black and white, 1 bit per pixel
+    Mono,
+    /// 4 levels of gray, 2 bits per pixel
+    ///
+    /// Each pixel is two panel pixels wide, so the display has half the
+    /// horizontal resolution.
+    Gray4,
+}
 
-        // Invert screen colors
-        self.invert_screen(self.inverted)?;
+/// Error returned by the driver
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Error<SpiE, PinE> {
+    /// Writing to the SPI bus failed
+    Spi(SpiE),
+    /// Setting the DC, CS or RST pin failed
+    Pin(PinE),
+    /// A coordinate, address or value is outside of the allowed range
+    OutOfBounds,
+    /// A buffer is too small or a slice has the wrong length
+    BufferSize,
+    /// Waiting for the tearing effect signal, but it isn't enabled
+    TearingDisabled,
+    /// [`ST7306::init()`] was called again, see [`ST7306::reinit()`]
+    AlreadyInitialized,
+    /// The configuration is invalid, see [`ST7306Builder::build()`]
+    Config(ConfigError),
+    /// Reading from the controller only returned zeros or ones, see
+    /// [`ST7306::new_and_probe_id()`]
+    NoResponse,
+}
 
-        self.on_off(true)?;
+impl<SpiE, PinE> From<ConfigError> for Error<SpiE, PinE> {
+    fn from(err: ConfigError) -> Self {
+        Error::Config(err)
+    }
+}
 
-        Ok(())
+/// Reset pin that does nothing
+///
+/// For boards where the RST line isn't connected to the MCU or is shared
+/// with another peripheral, together with
+/// [`ST7306Builder::external_reset()`]. `E` is the error type of the other
+/// pins, setting it never fails.
+pub struct NoPin<E>(PhantomData<E>);
+
+impl<E> NoPin<E> {
+    /// Create the pin
+    pub const fn new() -> Self {
+        NoPin(PhantomData)
     }
+}
 
-    /// Turn the screen on or off
-    pub fn on_off(&mut self, on: bool) -> Result<(), ()> {
-        if on {
-            self.write_command(Instruction::DISPON, &[])?;
-        } else {
-            self.write_command(Instruction::DISPOFF, &[])?;
-        }
-        self.display_on = on;
+impl<E> Default for NoPin<E> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<E> OutputPin for NoPin<E> {
+    type Error = E;
+
+    fn set_low(&mut self) -> Result<(), Self::Error> {
         Ok(())
     }
 
-    /// Have the display controller go into sleep mode
-    ///
-    /// Note: Must first go into HPM if currently in LPM, so after sleep_out,
-    /// if you want to be in LPM, need to manually go into LPM again.
-    pub fn sleep_in<DELAY>(&mut self, delay: &mut DELAY) -> Result<(), ()>
-    where
-        DELAY: DelayMs<u8>,
-    {
-        match self.power_mode {
-            PowerMode::Hpm => {
-                self.write_command(Instruction::SLPIN, &[])?;
-                delay.delay_ms(100);
-            }
-            PowerMode::Lpm => {
-                self.switch_mode(delay, PowerMode::Hpm)?;
-                delay.delay_ms(255);
-                self.sleep_in(delay)?;
-            }
-        }
-        self.sleeping = true;
+    fn set_high(&mut self) -> Result<(), Self::Error> {
         Ok(())
     }
+}
 
-    /// Wake the controller from sleep
-    pub fn sleep_out<DELAY>(&mut self, delay: &mut DELAY) -> Result<(), ()>
-    where
-        DELAY: DelayMs<u8>,
-    {
-        self.write_command(Instruction::SLPOUT, &[])?;
-        delay.delay_ms(100);
-        self.sleeping = false;
+#[cfg(feature = "async")]
+impl<E: embedded_hal_1::digital::Error> embedded_hal_1::digital::ErrorType for NoPin<E> {
+    type Error = E;
+}
+
+#[cfg(feature = "async")]
+impl<E: embedded_hal_1::digital::Error> embedded_hal_1::digital::OutputPin for NoPin<E> {
+    fn set_low(&mut self) -> Result<(), Self::Error> {
         Ok(())
     }
 
-    /// Switch between high and low power mode
-    pub fn switch_mode<DELAY>(
-        &mut self,
-        delay: &mut DELAY,
-        target_mode: PowerMode,
-    ) -> Result<(), ()>
-    where
-        DELAY: DelayMs<u8>,
-    {
-        if target_mode == self.power_mode {
-            return Ok(());
-        }
-        match target_mode {
-            PowerMode::Hpm => {
-                self.write_command(Instruction::HPM, &[])?;
-                delay.delay_ms(255);
-            }
-            PowerMode::Lpm => {
-                self.write_command(Instruction::LPM, &[])?;
-                delay.delay_ms(100);
-            }
-        }
-        self.power_mode = target_mode;
+    fn set_high(&mut self) -> Result<(), Self::Error> {
         Ok(())
     }
+}
 
-    /// Invert the colors on the screen
-    pub fn invert_screen(&mut self, inverted: bool) -> Result<(), ()> {
-        if inverted {
-            self.write_command(Instruction::INVON, &[])?;
-        } else {
-            self.write_command(Instruction::INVOFF, &[])?;
+/// Problem with the driver configuration, see [`ST7306::validate_config()`]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum ConfigError {
+    /// Width or height is zero
+    EmptyDisplay,
+    /// Width is not a multiple of 12 or height not a multiple of 2
+    ///
+    /// The controller addresses the RAM in 12x2 pixel cells, so the display
+    /// can only cover whole cells.
+    UnalignedSize,
+    /// The `COLS` x `ROWS` framebuffer has fewer cells than the display
+    FramebufferTooSmall,
+    /// The address window goes beyond the controller's last column or row
+    WindowOutOfRange,
+    /// The frame interval of the [`TuningConfig`] is larger than 3
+    InvalidFrameInterval,
+    /// The SPI frequency is higher than [`MAX_SPI_HZ`]
+    SpiTooFast,
+    /// The gate line count is zero or more than the controller supports
+    InvalidGateLines,
+    /// A code of the [`VoltageConfig`] is larger than the controller accepts
+    VoltageOutOfRange,
+}
+
+/// Configuration for an [`ST7306`] driver, with sensible defaults
+///
+/// By default the display is 720x400 pixels without an offset, not inverted,
+/// without auto power down and tearing pin, and refreshes at 1Hz in low and
+/// 32Hz in high power mode.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct ST7306Builder {
+    inverted: bool,
+    auto_power_down: bool,
+    tearing: bool,
+    fps: FpsConfig,
+    tuning: TuningConfig,
+    voltage: VoltageConfig,
+    timings: Timings,
+    nvm_load: NvmLoadCtrl,
+    first_gate: Option<u8>,
+    gate_lines: Option<u8>,
+    gate_timing: Option<[u8; 3]>,
+    gamma_voltage: Option<[u8; GAMMA_VOLTAGE_LEN]>,
+    width: u16,
+    height: u16,
+    col_start: u16,
+    row_start: u16,
+    spi_frequency: Option<u32>,
+    max_transfer: usize,
+    color_mode: ColorMode,
+    external_reset: bool,
+}
+
+impl Default for ST7306Builder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ST7306Builder {
+    /// Start with the default configuration
+    pub fn new() -> Self {
+        ST7306Builder {
+            inverted: false,
+            auto_power_down: false,
+            tearing: false,
+            fps: FpsConfig {
+                hpm: HpmFps::ThirtyTwo,
+                lpm: LpmFps::One,
+            },
+            tuning: TuningConfig::default(),
+            voltage: VoltageConfig::default(),
+            timings: Timings::default(),
+            nvm_load: NvmLoadCtrl::default(),
+            first_gate: None,
+            gate_lines: None,
+            gate_timing: None,
+            gamma_voltage: None,
+            width: (COL_MAX + 1) * PX_PER_COL,
+            height: (ROW_MAX + 1) * PX_PER_ROW,
+            col_start: 0,
+            row_start: 0,
+            spi_frequency: None,
+            max_transfer: usize::MAX,
+            color_mode: ColorMode::Mono,
+            external_reset: false,
         }
+    }
+
+    /// Invert the colors
+    pub fn inverted(mut self, inverted: bool) -> Self {
         self.inverted = inverted;
-        Ok(())
+        self
     }
 
-    /// Change the FPS config
-    ///
-    /// Note that to change to the desired FPS, you might have to switch between
-    /// low and high power modes.
-    pub fn set_fps(&mut self, fps: FpsConfig) -> Result<(), ()> {
+    /// Let the controller power down automatically
+    pub fn auto_power_down(mut self, auto_power_down: bool) -> Self {
+        self.auto_power_down = auto_power_down;
+        self
+    }
+
+    /// Enable the tearing effect pin
+    pub fn tearing(mut self, tearing: bool) -> Self {
+        self.tearing = tearing;
+        self
+    }
+
+    /// Frame rate in high and low power mode
+    pub fn fps(mut self, fps: FpsConfig) -> Self {
         self.fps = fps;
-        self.write_command(Instruction::FRCTRL, &[self.fps.as_u8()])?;
-        Ok(())
+        self
     }
 
-    /// Hard reset the controller by toggling the reset pin
-    fn hard_reset<DELAY>(&mut self, delay: &mut DELAY) -> Result<(), ()>
-    where
-        DELAY: DelayMs<u8>,
-    {
-        self.rst.set_high().map_err(|_| ())?;
-        delay.delay_ms(10);
+    /// Analog tuning values, see [`TuningConfig`]
+    pub fn tuning(mut self, tuning: TuningConfig) -> Self {
+        self.tuning = tuning;
+        self
+    }
+
+    /// Gate and source voltages, see [`VoltageConfig`]
+    pub fn voltage(mut self, voltage: VoltageConfig) -> Self {
+        self.voltage = voltage;
+        self
+    }
 
-        self.rst.set_low().map_err(|_| ())?;
-        delay.delay_ms(10);
+    /// What the controller loads from the NVM, see [`NvmLoadCtrl`]
+    pub fn nvm_load(mut self, nvm_load: NvmLoadCtrl) -> Self {
+        self.nvm_load = nvm_load;
+        self
+    }
 
-        self.rst.set_high().map_err(|_| ())
+    /// Delays of the init and power sequences, see [`Timings`]
+    pub fn timings(mut self, timings: Timings) -> Self {
+        self.timings = timings;
+        self
     }
 
-    /// Write a command with optional parameters
+    /// First gate line that the panel is scanned from (FSTCOM)
     ///
-    /// This function makes sure CS and DC pins are set correctly
-    pub fn write_command(&mut self, command: Instruction, params: &[u8]) -> Result<(), ()> {
-        self.cs.set_low().map_err(|_| ())?;
-        self.dc.set_low().map_err(|_| ())?;
-        self.spi.write(&[command as u8]).map_err(|_| ())?;
-        if !params.is_empty() {
-            self.start_data()?;
-            self.write_command_data(params)?;
-        }
-        self.cs.set_high().map_err(|_| ())?;
-        Ok(())
+    /// By default it isn't written and the controller's reset value is used.
+    /// See [`ST7306::set_first_gate()`].
+    pub fn first_gate(mut self, line: u8) -> Self {
+        self.first_gate = Some(line);
+        self
     }
 
-    /// Before writing data, the CS and DC pins must be set correctly
+    /// Gate timing (GTCON)
     ///
-    /// This command can be used if you want to write extra data, in addition
-    /// to a command's parameters.
-    pub fn start_data(&mut self) -> Result<(), ()> {
-        self.cs.set_low().map_err(|_| ())?;
-        self.dc.set_high().map_err(|_| ())
+    /// By default it isn't written, like in the reference code, and the
+    /// controller's reset value is used. The datasheet's init sequence sends
+    /// `[0x32, 0x03, 0x1F]`, try that on panels that show row artifacts.
+    /// See [`ST7306::set_gate_timing()`].
+    pub fn gate_timing(mut self, params: [u8; 3]) -> Self {
+        self.gate_timing = Some(params);
+        self
     }
 
-    /// Write data that's part of a command
+    /// Source gamma voltages (VSIKCTRL)
     ///
-    /// Either the command ID or the parameters.
-    fn write_command_data(&mut self, data: &[u8]) -> Result<(), ()> {
-        data.iter().fold(Ok(()), |res, byte| {
-            self.spi.write(&[*byte]).map_err(|_| ())?;
-            res
-        })
+    /// By default they aren't written and the controller's reset values are
+    /// used. See [`ST7306::set_gamma_voltage()`].
+    pub fn gamma_voltage(mut self, params: [u8; GAMMA_VOLTAGE_LEN]) -> Self {
+        self.gamma_voltage = Some(params);
+        self
     }
 
-    /// Write to the display controller's RAM
+    /// Number of gate lines the controller drives (GATESET)
     ///
-    /// The caller must first send a [`Instruction::RAMWR`] and can then call this
-    /// function repeatedly to fill the entire memory window.
+    /// By default it follows the height, one line for every 4 pixel lines,
+    /// rounded up. Set it explicitly for panels with a different number of
+    /// gate lines than visible pixels, at most 100.
+    pub fn gate_lines(mut self, lines: u8) -> Self {
+        self.gate_lines = Some(lines);
+        self
+    }
+
+    /// Size of the display in pixels
     ///
-    /// Must always write to RAM in 24 bit sequences, that's why the data
-    /// parameter accepts a slice of u8 triples.
-    pub fn write_ram(&mut self, data: &[(u8, u8, u8)]) -> Result<(), ()> {
-        data.iter().fold(Ok(()), |res, (first, second, third)| {
-            self.spi.write(&[*first]).map_err(|_| ())?;
-            self.spi.write(&[*second]).map_err(|_| ())?;
-            self.spi.write(&[*third]).map_err(|_| ())?;
-            res
-        })
+    /// Must be a multiple of 12 wide and of 2 high.
+    pub fn dimensions(mut self, width: u16, height: u16) -> Self {
+        self.width = width;
+        self.height = height;
+        self
     }
 
-    /// Clear the controller's RAM
+    /// Controller column and row where the display starts
     ///
-    /// Basically turns the screen all white
-    pub fn clear_ram(&mut self) -> Result<(), ()> {
-        self.on_off(false)?;
-        self.clear_ram_cmd(true)?;
-        self.on_off(true)?;
-        Ok(())
+    /// A column is 12 pixels wide and a row 2 pixels high.
+    pub fn offset(mut self, col_start: u16, row_start: u16) -> Self {
+        self.col_start = col_start;
+        self.row_start = row_start;
+        self
     }
 
-    /// Low level command, don't use if you don't know what you're doing
+    /// Frequency that the SPI bus was configured with
     ///
-    /// Before calling this, must call [`Self::on_off()`]
-    pub fn clear_ram_cmd(&mut self, clear: bool) -> Result<(), ()> {
-        let byte = 0b01001111;
-        let enable_clear_mask = 0b10000000;
+    /// The driver can't measure it, but checks it against [`MAX_SPI_HZ`].
+    pub fn spi_frequency(mut self, hz: u32) -> Self {
+        self.spi_frequency = Some(hz);
+        self
+    }
 
-        if clear {
-            self.write_command(Instruction::CLRAM, &[byte + enable_clear_mask])?;
-        } else {
-            // TODO: I don't know when there's a need to do this
-            self.write_command(Instruction::CLRAM, &[byte])?;
-        }
+    /// Longest SPI write in bytes
+    ///
+    /// Pixel data is split into writes of at most this many bytes, for SPI
+    /// peripherals whose DMA can only do limited transfers. Chip select
+    /// stays low in between, so the controller sees one continuous stream.
+    /// By default everything is sent in one write. Must be at least 1.
+    pub fn max_transfer(mut self, bytes: usize) -> Self {
+        self.max_transfer = bytes;
+        self
+    }
 
-        Ok(())
+    /// Mono or 4-level grayscale
+    ///
+    /// The dimensions are still in panel pixels, in Gray4 mode the driver
+    /// has half the width.
+    pub fn color_mode(mut self, color_mode: ColorMode) -> Self {
+        self.color_mode = color_mode;
+        self
     }
 
-    /// Not implemented yet!
-    pub fn set_orientation(&mut self, _orientation: &Orientation) -> Result<(), ()> {
-        panic!("TODO: Not yet implemented");
-        //self.write_command(Instruction::MADCTL, &[*orientation as u8])?;
-        //Ok(())
+    /// Leave resetting the controller to the caller
+    ///
+    /// For an RST line that is shared with another peripheral and must not
+    /// be toggled by the driver. [`ST7306::init()`] then skips the hard
+    /// reset and starts with the soft reset, so the caller must have
+    /// finished resetting the controller over the shared line before
+    /// calling it. If the pin isn't connected at all, pass a [`NoPin`].
+    pub fn external_reset(mut self) -> Self {
+        self.external_reset = true;
+        self
     }
 
-    /// Sets a pixel color at the given coords.
+    /// Check the configuration and create the driver
     ///
-    /// Changes the pixel value in the framebuffer at the bit where the
-    /// display controller expects it.
+    /// Does the same checks as [`ST7306::validate_config()`]. The display
+    /// isn't touched, call [`ST7306::init()`] next.
     ///
-    /// To show it on the display, call [`Self::flush()`].
-    pub fn set_pixel(&mut self, x: u16, y: u16, color: u8) -> Result<(), ()> {
-        let row = (y / PX_PER_ROW) as usize;
-        let col = (x / PX_PER_COL) as usize;
-        let black = color < 1;
-
-        let (byte, bitmask) = match (x % PX_PER_COL, y % PX_PER_ROW) {
-            (0, 0) => (0, 0x80),
-            (0, 1) => (0, 0x40),
-            (1, 0) => (0, 0x20),
-            (1, 1) => (0, 0x10),
-            (2, 0) => (0, 0x08),
-            (2, 1) => (0, 0x04),
-            (3, 0) => (0, 0x02),
-            (3, 1) => (0, 0x01),
-
-            (4, 0) => (1, 0x80),
-            (4, 1) => (1, 0x40),
-            (5, 0) => (1, 0x20),
-            (5, 1) => (1, 0x10),
-            (6, 0) => (1, 0x08),
-            (6, 1) => (1, 0x04),
-            (7, 0) => (1, 0x02),
-            (7, 1) => (1, 0x01),
-
-            (8, 0) => (2, 0x80),
-            (8, 1) => (2, 0x40),
-            (9, 0) => (2, 0x20),
-            (9, 1) => (2, 0x10),
-            (10, 0) => (2, 0x08),
-            (10, 1) => (2, 0x04),
-            (11, 0) => (2, 0x02),
-            (11, 1) => (2, 0x01),
-            _ => panic!("Impossible to reach"),
-        };
+    /// The driver holds the framebuffer, so it's as large as that: 36KB for
+    /// a 720x400 panel. Returned by value it's created on the stack first,
+    /// which can overflow the small stack of a microcontroller. For large
+    /// panels use [`Self::build_in_place()`] instead.
+    pub fn build<SPI, DC, CS, RST, const COLS: usize, const ROWS: usize>(
+        self,
+        spi: SPI,
+        dc: DC,
+        cs: CS,
+        rst: RST,
+    ) -> Result<ST7306<SPI, DC, CS, RST, COLS, ROWS>, ConfigError>
+    where
+        SPI: spi::Write<u8>,
+        DC: OutputPin,
+        CS: OutputPin<Error = DC::Error>,
+        RST: OutputPin<Error = DC::Error>,
+    {
+        self.check::<COLS, ROWS>()?;
+        Ok(ST7306::from_builder(spi, dc, cs, rst, &self))
+    }
 
-        if black {
-            self.framebuffer[row][col][byte] |= bitmask
-        } else {
-            self.framebuffer[row][col][byte] &= !bitmask;
+    /// Check the configuration and create the driver in `slot`
+    ///
+    /// Like [`Self::build()`], but the driver and its framebuffer are
+    /// initialized where they are, without a large temporary on the stack.
+    /// The slot is usually a `static`, for example from a `StaticCell`:
+    ///
+    /// ```ignore
+    /// static DISPLAY: StaticCell<MaybeUninit<ST7306<Spi, Dc, Cs, Rst, 60, 200>>> =
+    ///     StaticCell::new();
+    /// let slot = DISPLAY.init(MaybeUninit::uninit());
+    /// let display = ST7306Builder::new()
+    ///     .dimensions(720, 400)
+    ///     .build_in_place(slot, spi, dc, cs, rst)?;
+    /// ```
+    pub fn build_in_place<SPI, DC, CS, RST, const COLS: usize, const ROWS: usize>(
+        self,
+        slot: &mut MaybeUninit<ST7306<SPI, DC, CS, RST, COLS, ROWS>>,
+        spi: SPI,
+        dc: DC,
+        cs: CS,
+        rst: RST,
+    ) -> Result<&mut ST7306<SPI, DC, CS, RST, COLS, ROWS>, ConfigError>
+    where
+        SPI: spi::Write<u8>,
+        DC: OutputPin,
+        CS: OutputPin<Error = DC::Error>,
+        RST: OutputPin<Error = DC::Error>,
+    {
+        self.check::<COLS, ROWS>()?;
+        ST7306::write_from_builder(slot, spi, dc, cs, rst, &self);
+        // SAFETY: write_from_builder initializes every field
+        Ok(unsafe { slot.assume_init_mut() })
+    }
+
+    /// Checks of [`Self::build()`]
+    fn check<const COLS: usize, const ROWS: usize>(&self) -> Result<(), ConfigError> {
+        if self.spi_frequency.is_some_and(|hz| hz > MAX_SPI_HZ) {
+            return Err(ConfigError::SpiTooFast);
         }
-        Ok(())
+        check_config::<COLS, ROWS>(
+            self.width,
+            self.height,
+            &self.addr_window(),
+            &self.tuning,
+            &self.voltage,
+            self.gate_line_count(),
+        )
     }
-}
 
-#[cfg(feature = "graphics")]
-extern crate embedded_graphics;
-#[cfg(feature = "graphics")]
-use self::embedded_graphics::{
-    draw_target::DrawTarget,
-    pixelcolor::{
-        raw::{RawData, RawU16},
-        Rgb565,
-    },
-    prelude::*,
-};
+    /// GATESET line count, explicit or derived from the height
+    fn gate_line_count(&self) -> u16 {
+        match self.gate_lines {
+            Some(lines) => lines as u16,
+            None => self.height.div_ceil(PX_PER_GATE_LINE),
+        }
+    }
 
-fn col_to_bright(color: Rgb565) -> u8 {
-    ((color.r() as u16) + (color.g() as u16) + (color.b() as u16) / 3) as u8
-}
+    /// Controller columns that the display covers, inclusive
+    ///
+    /// Only meaningful for a width that is a multiple of 12, other widths
+    /// are rejected by [`Self::build()`] and [`ST7306::new()`], see
+    /// [`PanelGeometry::validate()`].
+    pub fn ram_columns(&self) -> RangeInclusive<u16> {
+        let window = self.addr_window();
+        window.col_start..=window.col_end
+    }
 
-#[cfg(feature = "graphics")]
-// TODO: Remove color support from here
-impl<SPI, DC, CS, RST, const COLS: usize, const ROWS: usize> DrawTarget
-    for ST7306<SPI, DC, CS, RST, COLS, ROWS>
-where
-    SPI: spi::Write<u8>,
-    DC: OutputPin,
-    CS: OutputPin,
-    RST: OutputPin,
-{
-    type Error = ();
-    type Color = Rgb565;
+    /// Controller rows that the display covers, inclusive
+    ///
+    /// Only meaningful for an even height, like [`Self::ram_columns()`].
+    pub fn ram_rows(&self) -> RangeInclusive<u16> {
+        let window = self.addr_window();
+        window.row_start..=window.row_end
+    }
 
-    fn draw_iter<I>(&mut self, pixels: I) -> Result<(), Self::Error>
-    where
-        I: IntoIterator<Item = Pixel<Self::Color>>,
+    /// RAM address window covering the display
+    fn addr_window(&self) -> AddrWindow {
+        PanelGeometry::ST7306.window(self.width, self.height, self.col_start, self.row_start)
+    }
+}
+
+/// Check the display geometry, tuning and voltages, see
+/// [`ST7306::validate_config()`]
+fn check_config<const COLS: usize, const ROWS: usize>(
+    width: u16,
+    height: u16,
+    addr_window: &AddrWindow,
+    tuning: &TuningConfig,
+    voltage: &VoltageConfig,
+    gate_lines: u16,
+) -> Result<(), ConfigError> {
+    PanelGeometry::ST7306.validate(width, height, addr_window.col_start, addr_window.row_start)?;
+    let has_framebuffer = COLS > 0 && ROWS > 0;
+    if has_framebuffer
+        && (COLS * (PX_PER_COL as usize) < width as usize
+            || ROWS * (PX_PER_ROW as usize) < height as usize)
     {
-        // ATTENTION!! After calling the draw functions, you have to flush.
-        // It doesn't auto flush because you might want to combine several draw
-        // operations together and flush them all at the same time. This avoids
-        // artifacts while the screen is refreshing.
-        // TODO: I think embedded-graphics has affordances for that.
-        self.draw_pixels(pixels, false)
+        return Err(ConfigError::FramebufferTooSmall);
+    }
+    if tuning.frame_interval > 0b11 {
+        return Err(ConfigError::InvalidFrameInterval);
     }
+    if gate_lines == 0 || gate_lines > GATE_LINES_MAX {
+        return Err(ConfigError::InvalidGateLines);
+    }
+    if !voltage.in_range() {
+        return Err(ConfigError::VoltageOutOfRange);
+    }
+    Ok(())
+}
 
-    //fn fill_contiguous<I>(&mut self, area: &Rectangle, colors: I) -> Result<(), Self::Error>
-    //where
-    //    I: IntoIterator<Item = Self::Color>,
-    //{
-    //    // Clamp area to drawable part of the display target
-    //    let drawable_area = area.intersection(&Rectangle::new(Point::zero(), self.size()));
-    //    let colors = area
-    //        .points()
-    //        .zip(colors)
-    //        .filter(|(pos, _color)| drawable_area.contains(*pos))
-    //        .map(|(_pos, color)| col_to_bright(color));
-    //    //let colors =
-    //    //        area.points()
-    //    //            .zip(colors)
-    //    //            .filter(|(pos, _color)| drawable_area.contains(*pos))
-    //    //            .map(|(_pos, color)| RawU16::from(color).into_inner());
-
-    //    if drawable_area.size != Size::zero() {
-    //        let ex = (drawable_area.top_left.x + (drawable_area.size.width - 1) as i32) as u16;
-    //        let ey = (drawable_area.top_left.y + (drawable_area.size.height - 1) as i32) as u16;
-    //        self.set_pixels_buffered_u8(
-    //            drawable_area.top_left.x as u16,
-    //            drawable_area.top_left.y as u16,
-    //            ex,
-    //            ey,
-    //            colors,
-    //        )?;
-    //    }
-
-    //    Ok(())
-    //}
+/// Driver without a framebuffer
+///
+/// With zero columns and rows the driver doesn't hold any pixels. It has no
+/// size to draw on, so frames have to be packed by the caller and sent with
+/// [`ST7306::write_frame()`]. Everything else, like init and power
+/// management, works the same.
+pub type ST7306Streaming<SPI, DC, CS, RST> = ST7306<SPI, DC, CS, RST, 0, 0>;
 
-    fn clear(&mut self, color: Self::Color) -> Result<(), Self::Error> {
-        let brightness = col_to_bright(color);
-        let black = if brightness < 128 { 0xFF } else { 0x00 };
+/// Number of framebuffer columns for a display `width` pixels wide
+///
+/// Use it for the `COLS` parameter of [`ST7306`], or [`st7306_for!`].
+pub const fn cols_for(width: u16) -> usize {
+    width.div_ceil(PX_PER_COL) as usize
+}
 
-        if black == 0xFF {
-            return self.clear_ram();
-        }
+/// Number of framebuffer rows for a display `height` pixels high
+///
+/// Use it for the `ROWS` parameter of [`ST7306`], or [`st7306_for!`].
+pub const fn rows_for(height: u16) -> usize {
+    height.div_ceil(PX_PER_ROW) as usize
+}
 
-        for col in 0..COLS {
-            for row in 0..ROWS {
-                self.framebuffer[row][col][0] = black;
-                self.framebuffer[row][col][1] = black;
-                self.framebuffer[row][col][2] = black;
-            }
-        }
-        self.flush()
-    }
+/// The [`ST7306`] type for a display size in pixels
+///
+/// Computes `COLS` and `ROWS` from the width and height, so they can't get
+/// out of sync with the size passed to [`ST7306Builder::dimensions()`].
+/// Without the SPI and pin types, they're inferred, which only works for
+/// the type of a variable.
+///
+/// ```ignore
+/// let display: st7306_for!(300, 400) = ST7306Builder::new()
+///     .dimensions(300, 400)
+///     .build(spi, dc, cs, rst)?;
+///
+/// struct App {
+///     display: st7306_for!(Spi, Dc, Cs, Rst, 300, 400),
+/// }
+/// ```
+#[macro_export]
+macro_rules! st7306_for {
+    ($width:expr, $height:expr) => {
+        $crate::ST7306<_, _, _, _, { $crate::cols_for($width) }, { $crate::rows_for($height) }>
+    };
+    ($spi:ty, $dc:ty, $cs:ty, $rst:ty, $width:expr, $height:expr) => {
+        $crate::ST7306<
+            $spi,
+            $dc,
+            $cs,
+            $rst,
+            { $crate::cols_for($width) },
+            { $crate::rows_for($height) },
+        >
+    };
 }
 
-#[cfg(feature = "graphics")]
-impl<SPI, DC, CS, RST, const COLS: usize, const ROWS: usize> OriginDimensions
-    for ST7306<SPI, DC, CS, RST, COLS, ROWS>
+/// ST7306 driver to connect to TFT displays.
+///
+/// The framebuffer has `COLS` columns of 12 pixels and `ROWS` rows of 2
+/// pixels, so it must be at least `width / 12` by `height / 2` big.
+/// [`cols_for()`] and [`rows_for()`], or the [`st7306_for!`] macro, compute
+/// them from the size.
+pub struct ST7306<SPI, DC, CS, RST, const COLS: usize, const ROWS: usize>
 where
     SPI: spi::Write<u8>,
     DC: OutputPin,
     CS: OutputPin,
     RST: OutputPin,
 {
-    fn size(&self) -> Size {
-        Size::new(self.width as u32, self.height as u32)
+    /// SPI
+    pub spi: SPI,
+
+    /// Data/command pin.
+    pub dc: DC,
+
+    /// Chip select pin
+    pub cs: CS,
+
+    /// Reset pin.
+    pub rst: RST,
+
+    /// Whether the colours are inverted (true) or not (false)
+    inverted: bool,
+
+    /// Internal framebuffer to keep pixels until flushing
+    framebuffer: [[[u8; 3]; COLS]; ROWS],
+
+    /// Off-screen framebuffer for the next frame, see [`Self::present()`]
+    #[cfg(feature = "double-buffer")]
+    back_buffer: [[[u8; 3]; COLS]; ROWS],
+
+    /// Whether the back buffer was borrowed since the last present
+    #[cfg(feature = "double-buffer")]
+    back_buffer_written: bool,
+
+    /// Auto power down
+    autopowerdown: bool,
+
+    /// Auto power down that was last written, which differs from the
+    /// configured value while idle, see [`Self::enter_idle()`]
+    autopowerdown_active: bool,
+
+    /// Enable tearing pin
+    te_enable: bool,
+
+    /// Gate line that raises the TE signal, see [`Self::set_tear_scanline()`]
+    tear_scanline: u16,
+
+    /// Frame rate configuration
+    fps: FpsConfig,
+
+    /// Analog tuning values
+    tuning: TuningConfig,
+
+    /// Gate and source voltages
+    voltage: VoltageConfig,
+
+    /// Delays of the init and power sequences
+    timings: Timings,
+
+    /// What the controller loads from the NVM
+    nvm_load: NvmLoadCtrl,
+
+    /// First gate line (FSTCOM), `None` to keep the controller's default
+    first_gate: Option<u8>,
+
+    /// Gate timing (GTCON), `None` to keep the controller's default
+    gate_timing: Option<[u8; 3]>,
+
+    /// Source gamma voltages (VSIKCTRL), `None` to keep the controller's
+    /// default
+    gamma_voltage: Option<[u8; GAMMA_VOLTAGE_LEN]>,
+
+    /// Number of gate lines (GATESET)
+    gate_lines: u8,
+
+    /// Memory Data Access Control byte
+    madctl: u8,
+
+    /// Mono or 4-level grayscale
+    color_mode: ColorMode,
+
+    /// Display width in pixels, in the current orientation
+    width: u16,
+
+    /// Display height in pixels, in the current orientation
+    height: u16,
+
+    /// Orientation that pixel coordinates are rotated by
+    orientation: Orientation,
+    addr_window: AddrWindow,
+
+    /// RAM address window that was last sent to the controller, `None` if
+    /// unknown, for example after a reset
+    ram_window: Option<AddrWindow>,
+
+    /// Whether currently sleeping
+    sleeping: bool,
+
+    /// Current power mode
+    power_mode: PowerMode,
+
+    /// Whether the display is currently on
+    display_on: bool,
+
+    /// Whether init has completed
+    initialized: bool,
+
+    /// Whether init leaves the hard reset to the caller
+    external_reset: bool,
+
+    /// Idle calls of auto_power before switching to LPM
+    auto_power_timeout: u16,
+
+    /// Consecutive auto_power calls without activity
+    idle_calls: u16,
+
+    /// Coalesce identical cells when flushing
+    rle_flush: bool,
+
+    /// Next framebuffer row of a flush in steps, `None` if none is running
+    step_row: Option<usize>,
+
+    /// Level of [`Self::set_brightness()`]
+    brightness: u8,
+
+    /// Level of [`Self::set_contrast()`]
+    contrast: u8,
+
+    /// Longest SPI write of pixel data, see [`ST7306Builder::max_transfer()`]
+    max_transfer: usize,
+
+    /// Incremented on every framebuffer change, see [`Self::generation()`]
+    generation: u64,
+
+    /// Framebuffer cells changed since the last flush, `None` if none did
+    dirty: Option<AddrWindow>,
+
+    /// Custom color to brightness mapping, see [`Self::set_luminance_fn()`]
+    #[cfg(feature = "graphics")]
+    luminance_fn: Option<fn(Rgb565) -> u8>,
+
+    /// How colors are turned into black and white, see
+    /// [`Self::set_dither_mode()`]
+    #[cfg(feature = "graphics")]
+    dither_mode: DitherMode,
+
+    /// Hash of each framebuffer row at the last [`Self::flush_rows()`],
+    /// `None` if the RAM content is unknown
+    #[cfg(feature = "row-hash")]
+    row_hashes: Option<[u32; ROWS]>,
+}
+
+/// Confirmation that the NVM should really be programmed
+///
+/// Required by [`ST7306::nvm_program()`], so it can't be called by
+/// accident. Programming the NVM can't be undone and wrong values can leave
+/// the panel unusable.
+#[derive(Debug)]
+pub struct NvmProgramConfirmation {
+    _private: (),
+}
+
+impl NvmProgramConfirmation {
+    /// Confirm that programming the NVM is irreversible and might brick the
+    /// panel
+    pub fn i_understand_this_is_irreversible() -> Self {
+        Self { _private: () }
+    }
+}
+
+/// Orientation of the display content, see [`ST7306::set_orientation()`]
+///
+/// The values are the MADCTL bits for the orientation, but the driver
+/// rotates in software.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Orientation {
+    Portrait = 0x00,
+    Landscape = 0x60,
+    PortraitSwapped = 0xC0,
+    LandscapeSwapped = 0xA0,
+}
+
+/// Pattern for bringing up a new panel, see [`ST7306::draw_test_pattern()`]
+///
+/// The patterns are in the panel's native orientation.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum TestPattern {
+    /// Alternating black and white 12x2 pixel cells
+    ///
+    /// Each cell is one RAM address, so a wrong address window or offset
+    /// shows up as shifted or broken squares.
+    Checkerboard,
+    /// Alternating black and white columns of one pixel
+    ///
+    /// Reveals if the bits within a cell end up on the wrong pixels.
+    VerticalStripes,
+    /// Alternating black and white lines of one pixel
+    HorizontalStripes,
+    /// All pixels black
+    SolidFill,
+}
+
+/// How far a flush in steps got, see [`ST7306::flush_step()`]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct FlushProgress {
+    /// Framebuffer rows sent so far
+    pub rows_sent: usize,
+    /// Framebuffer rows in total
+    pub rows_total: usize,
+}
+
+impl FlushProgress {
+    /// Whether the whole framebuffer was sent
+    pub fn is_done(&self) -> bool {
+        self.rows_sent >= self.rows_total
+    }
+}
+
+/// Register values that the driver last wrote, see [`ST7306::register_cache()`]
+///
+/// This is what was last written, not read from the device. After a reset
+/// the driver doesn't know the controller's values and the bytes show
+/// what [`ST7306::init()`] will write.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct RegisterCache {
+    /// Memory Data Access Control (MADCTL) byte
+    pub madctl: u8,
+    /// Frame Rate Control (FRCTRL) byte
+    pub frctrl: u8,
+    /// High power mode (HPM) or low power mode (LPM)
+    pub power_mode: PowerMode,
+    /// Inverted (INVON) or not (INVOFF)
+    pub inverted: bool,
+    /// In sleep mode (SLPIN) or not (SLPOUT)
+    pub sleeping: bool,
+    /// Display on (DISPON) or off (DISPOFF)
+    pub display_on: bool,
+    /// Tearing effect output enabled (TEON) or not (TEOFF)
+    pub tearing: bool,
+    /// Auto power down (AUTOPWRCTRL) enabled, also while idle, see
+    /// [`ST7306::enter_idle()`]
+    pub auto_power_down: bool,
+}
+
+/// Bits of the Memory Data Access Control (MADCTL) register
+///
+/// Controls the order in which the controller maps RAM to the panel. The
+/// default puts pixel 0,0 in the top left, see [`ST7306::set_madctl()`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct MadctlFlags {
+    /// Page address order (MY), flips the picture upside down
+    pub my: bool,
+    /// Column address order (MX), mirrors the picture horizontally
+    pub mx: bool,
+    /// Page/column order (MV), swaps rows and columns
+    pub mv: bool,
+    /// Data order (DO), see [`ST7306::set_data_order()`]
+    pub data_order: bool,
+    /// Gate scan order (GS), flips the order the gate lines are scanned in
+    pub gs: bool,
+}
+
+impl MadctlFlags {
+    const MY: u8 = 0b10000000;
+    const MX: u8 = 0b01000000;
+    const MV: u8 = 0b00100000;
+    const DO: u8 = MADCTL_DO;
+    const GS: u8 = 0b00000100;
+
+    /// Decode the flags from a MADCTL byte, other bits are ignored
+    pub fn from_bits(bits: u8) -> Self {
+        Self {
+            my: bits & Self::MY != 0,
+            mx: bits & Self::MX != 0,
+            mv: bits & Self::MV != 0,
+            data_order: bits & Self::DO != 0,
+            gs: bits & Self::GS != 0,
+        }
+    }
+
+    /// The byte to write to MADCTL
+    pub fn bits(&self) -> u8 {
+        let mut bits = 0;
+        for (set, bit) in [
+            (self.my, Self::MY),
+            (self.mx, Self::MX),
+            (self.mv, Self::MV),
+            (self.data_order, Self::DO),
+            (self.gs, Self::GS),
+        ] {
+            if set {
+                bits |= bit;
+            }
+        }
+        bits
+    }
+}
+
+impl Default for MadctlFlags {
+    fn default() -> Self {
+        Self::from_bits(MADCTL_DEFAULT)
+    }
+}
+
+impl<SPI, DC, CS, RST, const COLS: usize, const ROWS: usize> ST7306<SPI, DC, CS, RST, COLS, ROWS>
+where
+    SPI: spi::Write<u8>,
+    DC: OutputPin,
+    CS: OutputPin<Error = DC::Error>,
+    RST: OutputPin<Error = DC::Error>,
+{
+    /// Whether the driver holds a framebuffer, see [`ST7306Streaming`]
+    const HAS_FRAMEBUFFER: bool = COLS > 0 && ROWS > 0;
+
+    /// Creates a new driver instance that uses hardware SPI.
+    ///
+    /// Panics if [`PanelGeometry::validate()`] rejects the size and offset
+    /// or the display doesn't fit into the framebuffer. [`ST7306Builder`] is
+    /// easier to use and returns an error instead. For large panels see
+    /// [`ST7306Builder::build_in_place()`], the framebuffer returned by this
+    /// might not fit on the stack.
+    pub fn new(
+        spi: SPI,
+        dc: DC,
+        cs: CS,
+        rst: RST,
+        inverted: bool,
+        autopowerdown: bool,
+        te_enable: bool,
+        fps: FpsConfig,
+        width: u16,
+        height: u16,
+        col_start: u16,
+        row_start: u16,
+    ) -> Self {
+        let config = ST7306Builder::new()
+            .inverted(inverted)
+            .auto_power_down(autopowerdown)
+            .tearing(te_enable)
+            .fps(fps)
+            .dimensions(width, height)
+            .offset(col_start, row_start);
+        if let Err(err) = PanelGeometry::ST7306.validate(width, height, col_start, row_start) {
+            panic!("invalid display geometry: {:?}", err);
+        }
+        assert!(!Self::HAS_FRAMEBUFFER || COLS >= cols_for(width) && ROWS >= rows_for(height));
+
+        Self::from_builder(spi, dc, cs, rst, &config)
+    }
+
+    /// Create the driver from an already checked configuration
+    fn from_builder(spi: SPI, dc: DC, cs: CS, rst: RST, config: &ST7306Builder) -> Self {
+        let mut display = MaybeUninit::uninit();
+        Self::write_from_builder(&mut display, spi, dc, cs, rst, config);
+        // SAFETY: write_from_builder initializes every field
+        unsafe { display.assume_init() }
+    }
+
+    /// Initialize the driver in `slot` from an already checked configuration
+    ///
+    /// Writes every field where it is, the framebuffer is zeroed in place.
+    fn write_from_builder(
+        slot: &mut MaybeUninit<Self>,
+        spi: SPI,
+        dc: DC,
+        cs: CS,
+        rst: RST,
+        config: &ST7306Builder,
+    ) {
+        let width = match config.color_mode {
+            _ if !Self::HAS_FRAMEBUFFER => 0,
+            ColorMode::Mono => config.width,
+            ColorMode::Gray4 => config.width / (PX_PER_COL / GRAY_PX_PER_COL),
+        };
+        let height = if Self::HAS_FRAMEBUFFER {
+            config.height
+        } else {
+            0
+        };
+        let dirty = Self::HAS_FRAMEBUFFER.then_some(AddrWindow {
+            col_start: 0,
+            col_end: (COLS as u16).saturating_sub(1),
+            row_start: 0,
+            row_end: (ROWS as u16).saturating_sub(1),
+        });
+
+        let ptr = slot.as_mut_ptr();
+        // SAFETY: Only writes through pointers to the fields of the slot,
+        // without reading or dropping the uninitialized values.
+        unsafe {
+            addr_of_mut!((*ptr).spi).write(spi);
+            addr_of_mut!((*ptr).dc).write(dc);
+            addr_of_mut!((*ptr).cs).write(cs);
+            addr_of_mut!((*ptr).rst).write(rst);
+            addr_of_mut!((*ptr).inverted).write(config.inverted);
+            addr_of_mut!((*ptr).framebuffer).write_bytes(0, 1);
+            #[cfg(feature = "double-buffer")]
+            addr_of_mut!((*ptr).back_buffer).write_bytes(0, 1);
+            #[cfg(feature = "double-buffer")]
+            addr_of_mut!((*ptr).back_buffer_written).write(false);
+            addr_of_mut!((*ptr).fps).write(config.fps);
+            addr_of_mut!((*ptr).tuning).write(config.tuning);
+            addr_of_mut!((*ptr).voltage).write(config.voltage);
+            addr_of_mut!((*ptr).timings).write(config.timings);
+            addr_of_mut!((*ptr).nvm_load).write(config.nvm_load);
+            addr_of_mut!((*ptr).first_gate).write(config.first_gate);
+            addr_of_mut!((*ptr).gate_timing).write(config.gate_timing);
+            addr_of_mut!((*ptr).gamma_voltage).write(config.gamma_voltage);
+            let gate_lines = config.gate_line_count().min(GATE_LINES_MAX) as u8;
+            addr_of_mut!((*ptr).gate_lines).write(gate_lines);
+            addr_of_mut!((*ptr).madctl).write(MADCTL_DEFAULT);
+            addr_of_mut!((*ptr).color_mode).write(config.color_mode);
+            addr_of_mut!((*ptr).autopowerdown).write(config.auto_power_down);
+            addr_of_mut!((*ptr).autopowerdown_active).write(config.auto_power_down);
+            addr_of_mut!((*ptr).te_enable).write(config.tearing);
+            addr_of_mut!((*ptr).tear_scanline).write(0);
+            addr_of_mut!((*ptr).width).write(width);
+            addr_of_mut!((*ptr).height).write(height);
+            addr_of_mut!((*ptr).orientation).write(Orientation::Portrait);
+            addr_of_mut!((*ptr).sleeping).write(true);
+            addr_of_mut!((*ptr).power_mode).write(PowerMode::Hpm);
+            addr_of_mut!((*ptr).display_on).write(false);
+            addr_of_mut!((*ptr).initialized).write(false);
+            addr_of_mut!((*ptr).external_reset).write(config.external_reset);
+            addr_of_mut!((*ptr).auto_power_timeout).write(AUTO_POWER_TIMEOUT);
+            addr_of_mut!((*ptr).idle_calls).write(0);
+            addr_of_mut!((*ptr).addr_window).write(config.addr_window());
+            addr_of_mut!((*ptr).ram_window).write(None);
+            addr_of_mut!((*ptr).rle_flush).write(false);
+            addr_of_mut!((*ptr).step_row).write(None);
+            addr_of_mut!((*ptr).brightness).write(LEVEL_DEFAULT);
+            addr_of_mut!((*ptr).contrast).write(LEVEL_DEFAULT);
+            addr_of_mut!((*ptr).max_transfer).write(config.max_transfer.max(1));
+            addr_of_mut!((*ptr).generation).write(0);
+            addr_of_mut!((*ptr).dirty).write(dirty);
+            #[cfg(feature = "graphics")]
+            addr_of_mut!((*ptr).luminance_fn).write(None);
+            #[cfg(feature = "graphics")]
+            addr_of_mut!((*ptr).dither_mode).write(DitherMode::None);
+            #[cfg(feature = "row-hash")]
+            addr_of_mut!((*ptr).row_hashes).write(None);
+        }
+
+        // Doesn't compile if a field is added but not written above
+        let _ = |display: &Self| {
+            let ST7306 {
+                spi: _,
+                dc: _,
+                cs: _,
+                rst: _,
+                inverted: _,
+                framebuffer: _,
+                #[cfg(feature = "double-buffer")]
+                    back_buffer: _,
+                #[cfg(feature = "double-buffer")]
+                    back_buffer_written: _,
+                fps: _,
+                tuning: _,
+                voltage: _,
+                timings: _,
+                nvm_load: _,
+                first_gate: _,
+                gate_timing: _,
+                gamma_voltage: _,
+                gate_lines: _,
+                madctl: _,
+                color_mode: _,
+                autopowerdown: _,
+                autopowerdown_active: _,
+                te_enable: _,
+                tear_scanline: _,
+                width: _,
+                height: _,
+                orientation: _,
+                sleeping: _,
+                power_mode: _,
+                display_on: _,
+                initialized: _,
+                external_reset: _,
+                auto_power_timeout: _,
+                idle_calls: _,
+                addr_window: _,
+                ram_window: _,
+                rle_flush: _,
+                step_row: _,
+                brightness: _,
+                contrast: _,
+                max_transfer: _,
+                generation: _,
+                dirty: _,
+                #[cfg(feature = "graphics")]
+                    luminance_fn: _,
+                #[cfg(feature = "graphics")]
+                    dither_mode: _,
+                #[cfg(feature = "row-hash")]
+                    row_hashes: _,
+            } = display;
+        };
+    }
+
+    /// Creates a new driver instance and checks that the controller responds.
+    ///
+    /// Builds the driver from `config` and hard resets the controller with
+    /// `delay`. After the reset a NOP command is sent, so miswired or
+    /// misconfigured SPI and GPIO fail at construction instead of later,
+    /// while drawing. Returns [`Error::Config`] if the configuration is
+    /// invalid.
+    ///
+    /// This only proves that the write path works, not that a panel is
+    /// attached. With an SPI that can also read, use
+    /// [`Self::new_and_probe_id()`], which takes the same arguments and
+    /// reads the display ID instead.
+    ///
+    /// With [`ST7306Builder::external_reset()`] the hard reset is skipped.
+    pub fn new_and_probe<DELAY>(
+        spi: SPI,
+        dc: DC,
+        cs: CS,
+        rst: RST,
+        delay: &mut DELAY,
+        config: ST7306Builder,
+    ) -> Result<Self, Error<SPI::Error, DC::Error>>
+    where
+        DELAY: DelayMs<u8>,
+    {
+        let mut display = Self::build_and_reset(spi, dc, cs, rst, delay, config)?;
+        display.write_command(Instruction::NOP, &[])?;
+        Ok(display)
+    }
+
+    /// Build the driver and hard reset the controller, unless the reset is
+    /// external
+    fn build_and_reset<DELAY>(
+        spi: SPI,
+        dc: DC,
+        cs: CS,
+        rst: RST,
+        delay: &mut DELAY,
+        config: ST7306Builder,
+    ) -> Result<Self, Error<SPI::Error, DC::Error>>
+    where
+        DELAY: DelayMs<u8>,
+    {
+        let mut display = config.build(spi, dc, cs, rst)?;
+        if !display.external_reset {
+            display.hard_reset(delay)?;
+        }
+        Ok(display)
+    }
+
+    /// Check the configured SPI clock frequency
+    ///
+    /// The driver can't measure the SPI clock, so pass the frequency that the
+    /// SPI was configured with. Returns an error if it's faster than
+    /// [`MAX_SPI_HZ`], which the controller can't handle. The same check as
+    /// [`ST7306Builder::spi_frequency()`], for drivers created with
+    /// [`Self::new()`]. The driver is only borrowed, so it's still usable
+    /// after an error, for example to show a message on the panel.
+    pub fn with_spi_frequency(&self, hz: u32) -> Result<(), ConfigError> {
+        if hz > MAX_SPI_HZ {
+            return Err(ConfigError::SpiTooFast);
+        }
+        Ok(())
+    }
+
+    /// Check the whole configuration before calling [`Self::init()`]
+    ///
+    /// Checks the size and offset, the framebuffer size, the frame interval,
+    /// the gate lines and the voltage codes, see [`ConfigError`]. Returns
+    /// the first problem found, so an application can report a clear error
+    /// at startup instead of running into a panic or a garbled display
+    /// later.
+    pub fn validate_config(&self) -> Result<(), ConfigError> {
+        let (width, height) = if Self::HAS_FRAMEBUFFER {
+            let (width, height) = self.native_size();
+            (width * (PX_PER_COL / self.px_per_col()), height)
+        } else {
+            let (cols, rows) = self.window_cells();
+            (cols * PX_PER_COL, rows * PX_PER_ROW)
+        };
+        check_config::<COLS, ROWS>(
+            width,
+            height,
+            &self.addr_window,
+            &self.tuning,
+            &self.voltage,
+            self.gate_lines as u16,
+        )
+    }
+
+    /// Draw individual pixels
+    ///
+    /// Since the display controller doesn't have a command to send individual
+    /// pixels, we draw it to a framebuffer and then optionally flush all of
+    /// that to the contoller.
+    #[cfg(feature = "graphics")]
+    pub fn draw_pixels<I>(
+        &mut self,
+        pixels: I,
+        flush: bool,
+    ) -> Result<(), Error<SPI::Error, DC::Error>>
+    where
+        I: IntoIterator<Item = Pixel<Rgb565>>,
+    {
+        for Pixel(coord, color) in pixels.into_iter() {
+            // Only draw pixels that would be on screen
+            if coord.x >= 0
+                && coord.y >= 0
+                && coord.x < self.width as i32
+                && coord.y < self.height as i32
+            {
+                let brightness = self.luminance(color);
+                let (x, y) = (coord.x as u16, coord.y as u16);
+                match self.color_mode {
+                    ColorMode::Mono => {
+                        let black = self.dithered_black(x, y, brightness);
+                        self.write_pixel(x, y, black)
+                    }
+                    ColorMode::Gray4 => self.write_gray(x, y, 3 - (brightness >> 6)),
+                }
+            }
+        }
+        if flush {
+            self.flush()?;
+        }
+        Ok(())
+    }
+
+    /// Draw individual black and white pixels
+    ///
+    /// Like [`Self::draw_pixels()`], but `On` is black and `Off` white,
+    /// without converting to a brightness and back. The same as drawing
+    /// through [`Self::binary()`].
+    #[cfg(feature = "graphics")]
+    pub fn draw_pixels_binary<I>(
+        &mut self,
+        pixels: I,
+        flush: bool,
+    ) -> Result<(), Error<SPI::Error, DC::Error>>
+    where
+        I: IntoIterator<Item = Pixel<BinaryColor>>,
+    {
+        let bounding_box = self.bounding_box();
+        for Pixel(coord, color) in pixels.into_iter() {
+            if bounding_box.contains(coord) {
+                self.write_pixel(coord.x as u16, coord.y as u16, color.is_on());
+            }
+        }
+        if flush {
+            self.flush()?;
+        }
+        Ok(())
+    }
+
+    /// Draw a raw RGB565 image with its top left corner at `top_left`
+    ///
+    /// Colors are turned into black and white like everything else drawn
+    /// through the [`DrawTarget`] impl, so set a [`DitherMode`] for photos.
+    /// Parts outside of the display are skipped.
+    /// To show it on the display, call [`Self::flush()`].
+    #[cfg(feature = "graphics")]
+    pub fn draw_image(
+        &mut self,
+        image: &ImageRaw<'_, Rgb565>,
+        top_left: Point,
+    ) -> Result<(), Error<SPI::Error, DC::Error>> {
+        Image::new(image, top_left).draw(self)
+    }
+
+    /// Draw something and immediately flush it to the screen
+    ///
+    /// Convenient for one-off updates. When drawing multiple things, draw
+    /// them all first and flush once, that is a lot faster.
+    #[cfg(feature = "graphics")]
+    pub fn draw_and_flush<D>(&mut self, drawable: &D) -> Result<(), Error<SPI::Error, DC::Error>>
+    where
+        D: Drawable<Color = Rgb565>,
+    {
+        drawable.draw(self)?;
+        self.flush()
+    }
+
+    /// Draw something rotated by a multiple of 90 degrees
+    ///
+    /// Everything drawn by `drawable` is rotated clockwise around `origin`,
+    /// for example to put a sideways label onto an otherwise upright screen.
+    /// Pixels rotated off the display are skipped.
+    /// To show it on the display, call [`Self::flush()`].
+    #[cfg(feature = "graphics")]
+    pub fn draw_rotated<D>(
+        &mut self,
+        drawable: &D,
+        origin: Point,
+        rotation: Rotation90,
+    ) -> Result<(), Error<SPI::Error, DC::Error>>
+    where
+        D: Drawable<Color = Rgb565>,
+    {
+        drawable.draw(&mut Rotated {
+            target: self,
+            origin,
+            rotation,
+        })?;
+        Ok(())
+    }
+
+    /// Fill a rectangle with a dithered brightness gradient
+    ///
+    /// The brightness goes from `from` at the left/top of `area` to `to` at
+    /// the right/bottom, where 0 is black and 255 is white. It is turned into
+    /// black and white pixels with Floyd-Steinberg error diffusion, which
+    /// looks smoother than ordered dithering. The area is clamped to the
+    /// display.
+    ///
+    /// The error diffusion needs two rows of errors, which take up
+    /// `COLS * 48` bytes on the stack while filling.
+    /// To show it on the display, call [`Self::flush()`].
+    #[cfg(feature = "dither")]
+    pub fn fill_gradient(&mut self, area: Rectangle, from: u8, to: u8, direction: Axis) {
+        let area = area.intersection(&self.bounding_box());
+        let Some(bottom_right) = area.bottom_right() else {
+            return;
+        };
+        let (start, end) = match direction {
+            Axis::Horizontal => (area.top_left.x as usize, bottom_right.x as usize),
+            Axis::Vertical => (area.top_left.y as usize, bottom_right.y as usize),
+        };
+        let brightness = |pos: usize| -> i16 {
+            if end == start {
+                return from as i16;
+            }
+            // In i32, the product doesn't fit into i16 on wide areas
+            let step = (to as i32 - from as i32) * (pos - start) as i32 / (end - start) as i32;
+            (from as i32 + step) as i16
+        };
+
+        // Diffuse along the framebuffer's rows, which are only as wide as
+        // the panel in its native orientation
+        let area = self.native_area(area);
+        let bottom_right = area.bottom_right().unwrap_or(area.top_left);
+        let (x0, y0) = (area.top_left.x as usize, area.top_left.y as usize);
+        let (x1, y1) = (bottom_right.x as usize, bottom_right.y as usize);
+
+        // Errors diffused into the current and the next row
+        let mut current = [[0i16; PX_PER_COL as usize]; COLS];
+        let mut next = [[0i16; PX_PER_COL as usize]; COLS];
+        for y in y0..=y1 {
+            let errors = current.as_flattened_mut();
+            let next_errors = next.as_flattened_mut();
+            for x in x0..=x1 {
+                let (lx, ly) = self.logical_point(x as u16, y as u16);
+                let pos = match direction {
+                    Axis::Horizontal => lx,
+                    Axis::Vertical => ly,
+                };
+                let value = brightness(pos as usize) + errors[x];
+                let black = value < BLACK_THRESHOLD as i16;
+                let error = if black { value } else { value - 255 };
+
+                if x < x1 {
+                    errors[x + 1] += error * 7 / 16;
+                    next_errors[x + 1] += error / 16;
+                }
+                if x > x0 {
+                    next_errors[x - 1] += error * 3 / 16;
+                }
+                next_errors[x] += error * 5 / 16;
+
+                self.write_native_pixel(x as u16, y as u16, black);
+            }
+            current = next;
+            next = [[0; PX_PER_COL as usize]; COLS];
+        }
+    }
+
+    /// Draw with 4 gray levels
+    ///
+    /// Returns a [`DrawTarget`] with [`Gray2`] color. In [`ColorMode::Mono`]
+    /// the two darker levels are drawn black and the two lighter ones white.
+    /// To show it on the display, call [`Self::flush()`].
+    #[cfg(feature = "graphics")]
+    pub fn gray4(&mut self) -> Gray4Target<'_, SPI, DC, CS, RST, COLS, ROWS> {
+        Gray4Target { display: self }
+    }
+
+    /// Draw with 256 gray levels
+    ///
+    /// Returns a [`DrawTarget`] with [`Gray8`] color, for example for
+    /// anti-aliased text. In [`ColorMode::Mono`] the grays are always
+    /// dithered with the 4x4 Bayer matrix of [`DitherMode::Bayer4x4`], no
+    /// matter which dither mode is set, so smooth edges turn into a fine
+    /// pattern instead of jagged steps. In [`ColorMode::Gray4`] they're
+    /// rounded to the nearest of the 4 levels.
+    ///
+    /// Dithering happens in the framebuffer, where 0 is black. With
+    /// [`Self::invert_screen()`] the panel shows the complement, so a gray
+    /// of `g` shows up like `255 - g`, still dithered evenly.
+    /// To show it on the display, call [`Self::flush()`].
+    #[cfg(feature = "graphics")]
+    pub fn gray8(&mut self) -> Gray8Target<'_, SPI, DC, CS, RST, COLS, ROWS> {
+        Gray8Target { display: self }
+    }
+
+    /// Draw with [`BinaryColor`]
+    ///
+    /// Returns a [`DrawTarget`] that draws [`BinaryColor::On`] as black and
+    /// [`BinaryColor::Off`] as white pixels, without converting through
+    /// [`Rgb565`]. To show it on the display, call [`Self::flush()`].
+    #[cfg(feature = "graphics")]
+    pub fn binary(&mut self) -> BinaryTarget<'_, SPI, DC, CS, RST, COLS, ROWS> {
+        BinaryTarget { display: self }
+    }
+
+    /// Use a custom function to map colors to brightness
+    ///
+    /// The function receives the color drawn through the [`DrawTarget`] impl
+    /// and must return its brightness, from 0 (black) to 255 (white).
+    /// Pass `None` to go back to the default luma weighting.
+    #[cfg(feature = "graphics")]
+    pub fn set_luminance_fn(&mut self, luminance_fn: Option<fn(Rgb565) -> u8>) {
+        self.luminance_fn = luminance_fn;
+    }
+
+    /// Brightness of a color, using the custom mapping if one is set
+    #[cfg(feature = "graphics")]
+    fn luminance(&self, color: Rgb565) -> u8 {
+        match self.luminance_fn {
+            Some(luminance_fn) => luminance_fn(color),
+            None => col_to_bright(color),
+        }
+    }
+
+    /// Choose how colors are turned into black and white
+    ///
+    /// Applies to everything drawn through the [`DrawTarget`] impl in
+    /// [`ColorMode::Mono`]. Gray4 mode and already drawn pixels aren't
+    /// affected.
+    #[cfg(feature = "graphics")]
+    pub fn set_dither_mode(&mut self, dither_mode: DitherMode) {
+        self.dither_mode = dither_mode;
+    }
+
+    /// Current dither mode, see [`Self::set_dither_mode()`]
+    #[cfg(feature = "graphics")]
+    pub fn dither_mode(&self) -> DitherMode {
+        self.dither_mode
+    }
+
+    /// Whether a pixel of this brightness is drawn black in mono mode
+    #[cfg(feature = "graphics")]
+    fn dithered_black(&self, x: u16, y: u16, brightness: u8) -> bool {
+        match self.dither_mode {
+            DitherMode::None => brightness < BLACK_THRESHOLD,
+            DitherMode::Bayer4x4 => bayer_black(x, y, brightness),
+        }
+    }
+
+    /// Flush the changed part of the framebuffer to the screen
+    ///
+    /// Only sends the bounding box of the cells that changed since the last
+    /// flush, by setting the address window to it and restoring the full
+    /// window afterwards. Does nothing if nothing changed. If the bounding
+    /// box is so large that setting the window costs more than it saves, the
+    /// whole framebuffer is sent, like with [`Self::flush_full()`].
+    pub fn flush(&mut self) -> Result<(), Error<SPI::Error, DC::Error>> {
+        match self.flush_plan() {
+            FlushPlan::Skip => Ok(()),
+            FlushPlan::Full => self.flush_full(),
+            FlushPlan::Cells(dirty) => {
+                self.flush_cells(dirty)?;
+                self.dirty = None;
+                Ok(())
+            }
+        }
+    }
+
+    /// Decide what [`Self::flush()`] sends
+    ///
+    /// The changed cells, unless setting the window for them costs more
+    /// than sending everything.
+    fn flush_plan(&self) -> FlushPlan {
+        let Some(dirty) = self.dirty else {
+            return FlushPlan::Skip;
+        };
+        let cols = (dirty.col_end - dirty.col_start + 1) as usize;
+        let rows = (dirty.row_end - dirty.row_start + 1) as usize;
+        if self.partial_cheaper(cols, rows) {
+            FlushPlan::Cells(dirty)
+        } else {
+            FlushPlan::Full
+        }
+    }
+
+    /// Flush the part of the framebuffer that covers `area`
+    ///
+    /// For when it's known what changed, for example a digit of a clock.
+    /// The area is in pixels and clamped to the display, then widened to
+    /// whole cells of 12x2 pixels. Unlike [`Self::flush()`] it doesn't
+    /// look at what changed, those cells are always sent. Other changes
+    /// are still sent by the next [`Self::flush()`].
+    #[cfg(feature = "graphics")]
+    pub fn flush_area(&mut self, area: Rectangle) -> Result<(), Error<SPI::Error, DC::Error>> {
+        let area = area.intersection(&self.bounding_box());
+        if area.is_zero_sized() {
+            return Ok(());
+        }
+        let area = self.native_area(area);
+        let Some(bottom_right) = area.bottom_right() else {
+            return Ok(());
+        };
+        let px_per_col = self.px_per_col();
+        let cells = AddrWindow {
+            col_start: area.top_left.x as u16 / px_per_col,
+            col_end: bottom_right.x as u16 / px_per_col,
+            row_start: area.top_left.y as u16 / PX_PER_ROW,
+            row_end: bottom_right.y as u16 / PX_PER_ROW,
+        };
+        self.flush_cells(cells)?;
+
+        // Nothing else to flush if all changes were inside of the area
+        if self.dirty.is_some_and(|dirty| {
+            dirty.col_start >= cells.col_start
+                && dirty.col_end <= cells.col_end
+                && dirty.row_start >= cells.row_start
+                && dirty.row_end <= cells.row_end
+        }) {
+            self.dirty = None;
+        }
+        Ok(())
+    }
+
+    /// Send a window of framebuffer cells, inclusive
+    fn flush_cells(&mut self, cells: AddrWindow) -> Result<(), Error<SPI::Error, DC::Error>> {
+        let window = self.cells_window(cells);
+        self.write_columns(window.col_start, window.col_end)?;
+        self.write_rows(window.row_start, window.row_end)?;
+        self.write_command(Instruction::RAMWR, &[])?;
+        self.start_data()?;
+        for row in cells.row_start as usize..=cells.row_end as usize {
+            self.write_framebuffer_cells(row, cells.col_start as usize, cells.col_end as usize)?;
+        }
+
+        // Full flushes expect the full window
+        self.write_full_window()
+    }
+
+    /// RAM address window of framebuffer cells, with the display's offset
+    fn cells_window(&self, cells: AddrWindow) -> AddrWindow {
+        let window = self.addr_window;
+        AddrWindow {
+            col_start: window.col_start + cells.col_start,
+            col_end: window.col_start + cells.col_end,
+            row_start: window.row_start + cells.row_start,
+            row_end: window.row_start + cells.row_end,
+        }
+    }
+
+    /// Flush the entire framebuffer to the screen
+    ///
+    /// Sends every cell, even if it didn't change. Needed if the RAM content
+    /// was changed behind the driver's back, for example with
+    /// [`Self::write_ram()`].
+    pub fn flush_full(&mut self) -> Result<(), Error<SPI::Error, DC::Error>> {
+        if self.ram_window != Some(self.addr_window) {
+            self.write_full_window()?;
+        }
+        self.write_command(Instruction::RAMWR, &[])?;
+        self.start_data()?;
+
+        if self.rle_flush {
+            self.write_framebuffer_rle()?;
+        } else {
+            let data = self.framebuffer.as_flattened().as_flattened();
+            write_chunked(&mut self.spi, data, self.max_transfer).map_err(Error::Spi)?;
+        }
+        self.flushed_full();
+        Ok(())
+    }
+
+    /// Track that the whole framebuffer was sent
+    fn flushed_full(&mut self) {
+        self.dirty = None;
+        // Also finishes a flush in steps
+        self.step_row = None;
+    }
+
+    /// Flush the entire framebuffer a few rows at a time
+    ///
+    /// Sends the next `rows` framebuffer rows, each 2 pixels high, and
+    /// returns how far it got. Call it again until the progress
+    /// [`is_done()`](FlushProgress::is_done), for example once per loop of a
+    /// main loop that also has to feed a watchdog. The first call sends the
+    /// address window and RAMWR, later ones continue with WRMEMC, see
+    /// [`Self::write_ram_continue()`].
+    ///
+    /// Until it's done, don't send other commands that write to the RAM or
+    /// change the address window, like another flush, or the rest of the
+    /// image ends up in the wrong place. Changing the framebuffer in between
+    /// is fine, but rows that were already sent only show the change after
+    /// the next flush.
+    pub fn flush_step(
+        &mut self,
+        rows: usize,
+    ) -> Result<FlushProgress, Error<SPI::Error, DC::Error>> {
+        let start = match self.step_row {
+            Some(row) => {
+                self.write_command(Instruction::WRMEMC, &[])?;
+                row
+            }
+            None => {
+                if self.ram_window != Some(self.addr_window) {
+                    self.write_full_window()?;
+                }
+                self.write_command(Instruction::RAMWR, &[])?;
+                0
+            }
+        };
+        let end = start.saturating_add(rows.max(1)).min(ROWS);
+        self.start_data()?;
+        let data = self.framebuffer[start..end].as_flattened().as_flattened();
+        write_chunked(&mut self.spi, data, self.max_transfer).map_err(Error::Spi)?;
+
+        if end < ROWS {
+            self.step_row = Some(end);
+        } else {
+            self.step_row = None;
+            self.dirty = None;
+        }
+        Ok(FlushProgress {
+            rows_sent: end,
+            rows_total: ROWS,
+        })
+    }
+
+    /// Flush and wait until the panel has shown the new content
+    ///
+    /// The panel only picks up the new RAM content on its next refresh. In
+    /// low power mode at 1Hz that's up to a second later, so if the MCU goes
+    /// to sleep right after flushing the old image might stay on screen.
+    /// This waits for one full frame at the current frame rate after
+    /// flushing. In high power mode that's only a few milliseconds.
+    pub fn flush_and_wait_refresh<DELAY>(
+        &mut self,
+        delay: &mut DELAY,
+    ) -> Result<(), Error<SPI::Error, DC::Error>>
+    where
+        DELAY: DelayMs<u8>,
+    {
+        self.flush()?;
+        delay_ms(delay, self.frame_period_ms());
+        Ok(())
+    }
+
+    /// Wait for the start of the vertical blanking period
+    ///
+    /// Busy-waits for a rising edge of the tearing effect (TE) signal. The
+    /// controller raises it when it finished scanning out a frame, writing
+    /// RAM right afterwards doesn't tear. The driver enables it with the
+    /// V-blanking only mode (TEON 0x00). With V and H-blanking (0x01) the
+    /// line would also pulse after every gate line, so an edge wouldn't mean
+    /// the start of a frame.
+    ///
+    /// The panel is scanned from the line set by [`Self::set_scroll_start()`],
+    /// so with scrolling the blanking period is still between frames, but
+    /// the first line written is no longer the first one shown.
+    ///
+    /// In low power mode at 1Hz this waits up to a second. Returns
+    /// [`Error::TearingDisabled`] if [`ST7306Builder::tearing()`] isn't
+    /// enabled, because the signal would never come.
+    pub fn wait_for_te<TE>(&mut self, te: &TE) -> Result<(), Error<SPI::Error, DC::Error>>
+    where
+        TE: InputPin<Error = DC::Error>,
+    {
+        if !self.te_enable {
+            return Err(Error::TearingDisabled);
+        }
+        // Might already be in the blanking period, wait for the next one
+        while te.is_high().map_err(Error::Pin)? {}
+        while te.is_low().map_err(Error::Pin)? {}
+        Ok(())
+    }
+
+    /// Flush after waiting for the vertical blanking period
+    ///
+    /// Like [`Self::flush()`], but synchronized to the tearing effect pin,
+    /// see [`Self::wait_for_te()`]. The SPI has to be fast enough to write
+    /// the changes before the scan reaches them, otherwise the bottom of the
+    /// screen can still tear.
+    pub fn flush_synced<TE>(&mut self, te: &TE) -> Result<(), Error<SPI::Error, DC::Error>>
+    where
+        TE: InputPin<Error = DC::Error>,
+    {
+        if self.dirty.is_none() {
+            return Ok(());
+        }
+        self.wait_for_te(te)?;
+        self.flush()
+    }
+
+    /// Flush the entire framebuffer, first the even and then the odd rows
+    ///
+    /// On a slow SPI bus a regular flush visibly wipes the new image in from
+    /// top to bottom. Interlacing makes it appear on the whole screen at
+    /// once, first at half the vertical resolution. The tradeoff is that
+    /// every row needs its own RASET and RAMWR command, so a few hundred more
+    /// bytes are sent than with [`Self::flush()`].
+    pub fn flush_interlaced(&mut self) -> Result<(), Error<SPI::Error, DC::Error>> {
+        self.write_full_window()?;
+        for first_row in [0, 1] {
+            for row in (first_row..ROWS).step_by(2) {
+                let row_addr = self.addr_window.row_start + row as u16;
+                self.write_rows(row_addr, row_addr)?;
+                self.write_command(Instruction::RAMWR, &[])?;
+                self.start_data()?;
+                self.write_framebuffer_row(row)?;
+            }
+        }
+        self.dirty = None;
+        // Regular flushes expect the full window
+        self.write_full_window()
+    }
+
+    /// Flush only the framebuffer rows that changed since the last call
+    ///
+    /// Compares a hash of each row with the one from the last call and
+    /// writes each run of changed rows with its own RASET and RAMWR. Unlike
+    /// [`Self::flush()`], which sends one box around all changes, two
+    /// changed rows far apart don't send the rows between them. Needs 4
+    /// bytes of RAM per row for the hashes.
+    ///
+    /// The first call, and the first after [`Self::invalidate_row_hashes()`]
+    /// or [`Self::mark_all_dirty()`], sends every row.
+    #[cfg(feature = "row-hash")]
+    pub fn flush_rows(&mut self) -> Result<(), Error<SPI::Error, DC::Error>> {
+        let old_hashes = self.row_hashes;
+        let mut hashes = [0; ROWS];
+        for (hash, row) in hashes.iter_mut().zip(self.framebuffer.iter()) {
+            *hash = row_hash(row.as_flattened());
+        }
+        let changed = |row: usize| old_hashes.is_none_or(|old| old[row] != hashes[row]);
+
+        let mut row = 0;
+        let mut windowed = false;
+        while row < ROWS {
+            if !changed(row) {
+                row += 1;
+                continue;
+            }
+            let run_end = (row..ROWS)
+                .take_while(|&r| changed(r))
+                .last()
+                .unwrap_or(row);
+            let row_start = self.addr_window.row_start + row as u16;
+            let row_end = self.addr_window.row_start + run_end as u16;
+            self.write_rows(row_start, row_end)?;
+            self.write_command(Instruction::RAMWR, &[])?;
+            self.start_data()?;
+            for r in row..=run_end {
+                self.write_framebuffer_row(r)?;
+            }
+            windowed = true;
+            row = run_end + 1;
+        }
+
+        self.row_hashes = Some(hashes);
+        self.dirty = None;
+        if windowed {
+            // Regular flushes expect the full window
+            self.write_full_window()?;
+        }
+        Ok(())
+    }
+
+    /// Make the next [`Self::flush_rows()`] send every row
+    ///
+    /// Needed if the RAM content changed without the driver knowing, for
+    /// example after [`Self::write_ram()`]. Changing the orientation doesn't
+    /// need it, the framebuffer rows stay in the native orientation.
+    #[cfg(feature = "row-hash")]
+    pub fn invalidate_row_hashes(&mut self) {
+        self.row_hashes = None;
+    }
+
+    /// Hand the framebuffer to `sink` instead of sending it to the display
+    ///
+    /// Useful to mirror the display somewhere else, for example to a
+    /// companion app over USB, or to record it. Nothing is sent over SPI.
+    ///
+    /// The sink is called once per framebuffer row, top to bottom, with the
+    /// exact bytes that [`Self::flush_full()`] sends after RAMWR. A row
+    /// covers two lines of pixels and has 3 bytes for each 12x2 pixel cell,
+    /// left to right. Within a cell, each byte holds 4 columns of 2 pixels.
+    /// Going from the MSB, the bits are: column 0 top, column 0 bottom,
+    /// column 1 top, and so on. A set bit is a black pixel.
+    pub fn flush_to(&self, sink: &mut impl FnMut(&[u8])) {
+        for row in self.framebuffer.iter() {
+            sink(row.as_flattened());
+        }
+    }
+
+    /// Whether flushing only `area` is cheaper than flushing everything
+    ///
+    /// Assumes that the time it takes to flush is proportional to the number
+    /// of bytes sent over SPI, including the command bytes. A partial flush
+    /// writes the whole 12x2 pixel cells that the area touches, but also has
+    /// to set the address window to the area and restore it afterwards. The
+    /// area is clamped to the display, an empty area is always worthwhile.
+    #[cfg(feature = "graphics")]
+    pub fn partial_flush_worthwhile(&self, area: Rectangle) -> bool {
+        let area = area.intersection(&self.bounding_box());
+        if area.is_zero_sized() {
+            return true;
+        }
+        let area = self.native_area(area);
+        let Some(bottom_right) = area.bottom_right() else {
+            return true;
+        };
+        let px_per_col = self.px_per_col() as usize;
+        let cols =
+            (bottom_right.x as usize / px_per_col) - (area.top_left.x as usize / px_per_col) + 1;
+        let rows = (bottom_right.y as usize / PX_PER_ROW as usize)
+            - (area.top_left.y as usize / PX_PER_ROW as usize)
+            + 1;
+        self.partial_cheaper(cols, rows)
+    }
+
+    /// Whether flushing `cols` x `rows` cells costs less than a full flush
+    fn partial_cheaper(&self, cols: usize, rows: usize) -> bool {
+        partial_flush_bytes(cols, rows) < full_flush_bytes::<COLS, ROWS>()
+    }
+
+    /// How many bytes the next [`Self::flush()`] sends over SPI
+    ///
+    /// Counts the command bytes and their parameters as well as the pixel
+    /// data. Zero if nothing changed. Divide by the SPI clock in bytes per
+    /// second to estimate how long the flush takes.
+    pub fn flush_byte_count(&self) -> usize {
+        match self.flush_plan() {
+            FlushPlan::Skip => 0,
+            FlushPlan::Cells(dirty) => {
+                let cols = (dirty.col_end - dirty.col_start + 1) as usize;
+                let rows = (dirty.row_end - dirty.row_start + 1) as usize;
+                partial_flush_bytes(cols, rows)
+            }
+            FlushPlan::Full => {
+                let window = if self.ram_window == Some(self.addr_window) {
+                    0
+                } else {
+                    WINDOW_BYTES
+                };
+                window + full_flush_bytes::<COLS, ROWS>()
+            }
+        }
+    }
+
+    /// Write one row of the framebuffer to RAM
+    fn write_framebuffer_row(&mut self, row: usize) -> Result<(), Error<SPI::Error, DC::Error>> {
+        self.write_framebuffer_cells(row, 0, COLS - 1)
+    }
+
+    /// Write the cells `col_start` to `col_end` of a framebuffer row to RAM
+    fn write_framebuffer_cells(
+        &mut self,
+        row: usize,
+        col_start: usize,
+        col_end: usize,
+    ) -> Result<(), Error<SPI::Error, DC::Error>> {
+        let data = self.framebuffer[row][col_start..=col_end].as_flattened();
+        write_chunked(&mut self.spi, data, self.max_transfer).map_err(Error::Spi)
+    }
+
+    /// Set the columns of the RAM address window
+    ///
+    /// Takes pixel coordinates, which are widened to full columns of 12
+    /// pixels, or 6 in Gray4 mode.
+    /// Leaves the rows untouched. Returns an error if the range is empty or
+    /// goes beyond the controller's last column.
+    ///
+    /// Must be followed by a [`Instruction::RAMWR`] and [`Self::write_ram()`]
+    /// with exactly the number of cells in the window. Flushing sets the
+    /// window back to the whole display.
+    pub fn set_column_window(
+        &mut self,
+        x_start: u16,
+        x_end: u16,
+    ) -> Result<(), Error<SPI::Error, DC::Error>> {
+        let col_start = self.addr_window.col_start + x_start / self.px_per_col();
+        let col_end = self.addr_window.col_start + x_end / self.px_per_col();
+        if x_start > x_end || col_end > COL_MAX {
+            return Err(Error::OutOfBounds);
+        }
+        self.write_columns(col_start, col_end)
+    }
+
+    /// Set the rows of the RAM address window
+    ///
+    /// Takes pixel coordinates, which are widened to full 2 pixel rows.
+    /// Leaves the columns untouched. Returns an error if the range is empty
+    /// or goes beyond the controller's last row.
+    ///
+    /// The same caveats as for [`Self::set_column_window()`] apply.
+    pub fn set_row_window(
+        &mut self,
+        y_start: u16,
+        y_end: u16,
+    ) -> Result<(), Error<SPI::Error, DC::Error>> {
+        let row_start = self.addr_window.row_start + y_start / PX_PER_ROW;
+        let row_end = self.addr_window.row_start + y_end / PX_PER_ROW;
+        if y_start > y_end || row_end > ROW_MAX {
+            return Err(Error::OutOfBounds);
+        }
+        self.write_rows(row_start, row_end)
+    }
+
+    /// Set the RAM address window in cells
+    ///
+    /// Columns are 12 pixels wide, or 6 in Gray4 mode, and rows 2 pixels
+    /// high. They're counted from the top left of the display, the
+    /// configured offset is added by the driver. Returns an error if a range
+    /// is empty or goes beyond the display.
+    ///
+    /// Must be followed by a [`Instruction::RAMWR`] and [`Self::write_ram()`]
+    /// with exactly the number of cells in the window. Flushing sets the
+    /// window back to the whole display, or call
+    /// [`Self::full_address_window()`].
+    pub fn set_address_window(
+        &mut self,
+        col_start: u16,
+        col_end: u16,
+        row_start: u16,
+        row_end: u16,
+    ) -> Result<(), Error<SPI::Error, DC::Error>> {
+        let (cols, rows) = self.window_cells();
+        if col_start > col_end || col_end >= cols || row_start > row_end || row_end >= rows {
+            return Err(Error::OutOfBounds);
+        }
+        let window = self.addr_window;
+        self.write_columns(window.col_start + col_start, window.col_start + col_end)?;
+        self.write_rows(window.row_start + row_start, window.row_start + row_end)
+    }
+
+    /// Set the RAM address window to the whole display
+    ///
+    /// Undoes [`Self::set_address_window()`], [`Self::set_column_window()`]
+    /// and [`Self::set_row_window()`].
+    pub fn full_address_window(&mut self) -> Result<(), Error<SPI::Error, DC::Error>> {
+        self.write_full_window()
+    }
+
+    /// Set the RAM address window to the whole display
+    fn write_full_window(&mut self) -> Result<(), Error<SPI::Error, DC::Error>> {
+        let window = self.addr_window;
+        // Columns 18-42 (S217-S516). 25 columns, one for 12 pixels => 300px
+        self.write_columns(window.col_start, window.col_end)?;
+        // Rows 0-199 (G1-G402). 200 rows, one for 2 pixels => 400px
+        self.write_rows(window.row_start, window.row_end)?;
+        self.ram_window = Some(window);
+        Ok(())
+    }
+
+    /// Send CASET with controller column addresses
+    fn write_columns(&mut self, start: u16, end: u16) -> Result<(), Error<SPI::Error, DC::Error>> {
+        self.write_command(Instruction::CASET, &[start as u8, end as u8])?;
+        self.columns_written(start, end);
+        Ok(())
+    }
+
+    /// Send RASET with controller row addresses
+    fn write_rows(&mut self, start: u16, end: u16) -> Result<(), Error<SPI::Error, DC::Error>> {
+        self.write_command(Instruction::RASET, &[start as u8, end as u8])?;
+        self.rows_written(start, end);
+        Ok(())
+    }
+
+    /// Track the columns of the RAM window after CASET
+    fn columns_written(&mut self, start: u16, end: u16) {
+        self.ram_window = self.ram_window.map(|window| AddrWindow {
+            col_start: start,
+            col_end: end,
+            ..window
+        });
+    }
+
+    /// Track the rows of the RAM window after RASET
+    fn rows_written(&mut self, start: u16, end: u16) {
+        self.ram_window = self.ram_window.map(|window| AddrWindow {
+            row_start: start,
+            row_end: end,
+            ..window
+        });
+    }
+
+    /// Coalesce runs of identical cells when flushing
+    ///
+    /// The controller has no command to repeat data, so runs are still sent
+    /// in full, each run of up to 16 identical cells in a single SPI write.
+    /// RLE never saves bytes: a full flush sends exactly the same bytes
+    /// either way, just split up differently. A plain full flush sends the
+    /// pixel data in writes of [`ST7306Builder::max_transfer()`] bytes,
+    /// with RLE each write is at most 48 bytes, so it never takes fewer
+    /// writes than a `max_transfer` of 48 would. [`Self::rle_write_count()`]
+    /// tells how many writes the current image takes.
+    ///
+    /// It's only worth it if the SPI HAL can't send the framebuffer
+    /// directly, for example because DMA can't read the memory it's in, and
+    /// the short patterns can be sent from a buffer the DMA can read. On a
+    /// blank or mostly uniform image that's about one write per 16 cells,
+    /// on a busy image it's close to one write per cell. Otherwise prefer a
+    /// plain flush with a large `max_transfer`.
+    pub fn set_rle_flush(&mut self, enable: bool) {
+        self.rle_flush = enable;
+    }
+
+    /// How many SPI writes of pixel data a full flush with RLE takes
+    ///
+    /// Compare with `COLS * ROWS * 3 / max_transfer` writes, rounded up, of
+    /// a plain full flush to decide whether [`Self::set_rle_flush()`] is
+    /// worth it for the images the application shows. Both send the same
+    /// number of bytes.
+    pub fn rle_write_count(&self) -> usize {
+        rle_runs(self.framebuffer.as_flattened()).count()
+    }
+
+    /// Write the whole framebuffer to RAM, coalescing identical cells
+    fn write_framebuffer_rle(&mut self) -> Result<(), Error<SPI::Error, DC::Error>> {
+        for (cell, run) in rle_runs(self.framebuffer.as_flattened()) {
+            let pattern = [cell; RLE_CHUNK_CELLS];
+            self.spi
+                .write(pattern[..run].as_flattened())
+                .map_err(Error::Spi)?;
+        }
+        Ok(())
+    }
+
+    /// Number of times the framebuffer has changed
+    ///
+    /// Every change of a framebuffer bit increments this counter, writing a
+    /// pixel with the color it already has doesn't. It's a `u64`, so it won't
+    /// wrap around in the lifetime of the device.
+    pub fn generation(&self) -> u64 {
+        self.generation
+    }
+
+    /// The framebuffer, in the layout that the controller expects
+    ///
+    /// It's indexed by row, then column, then byte. Each row covers 2 lines
+    /// of pixels and each column 12 pixels, in the panel's native
+    /// orientation, so `[row][col]` holds the pixels from `x = col * 12` and
+    /// `y = row * 2`. Going from the MSB of byte 0 to the LSB of byte 2, the
+    /// bits are (x, y), (x, y + 1), (x + 1, y), (x + 1, y + 1), and so on
+    /// up to (x + 11, y + 1). A set bit is a black pixel.
+    ///
+    /// In [`ColorMode::Gray4`] a column is 6 pixels wide and each pixel has
+    /// two neighbouring bits in the same order, the MSB first. Both bits set
+    /// is black.
+    pub fn framebuffer(&self) -> &[[[u8; 3]; COLS]; ROWS] {
+        &self.framebuffer
+    }
+
+    /// The framebuffer, to write packed bytes directly
+    ///
+    /// See [`Self::framebuffer()`] for the layout. The driver doesn't notice
+    /// these changes, so call [`Self::mark_all_dirty()`] afterwards.
+    pub fn framebuffer_mut(&mut self) -> &mut [[[u8; 3]; COLS]; ROWS] {
+        &mut self.framebuffer
+    }
+
+    /// The off-screen framebuffer, to build the next frame in
+    ///
+    /// Same layout as [`Self::framebuffer()`]. Drawing through the driver
+    /// still goes to the front framebuffer, write the packed bytes here and
+    /// show them with [`Self::present()`].
+    ///
+    /// Each framebuffer takes `COLS * ROWS * 3` bytes, which is 36KB for a
+    /// 720x400 panel, so double buffering needs 72KB. Keep the driver in a
+    /// `static` rather than on the stack.
+    #[cfg(feature = "double-buffer")]
+    pub fn back_buffer_mut(&mut self) -> &mut [[[u8; 3]; COLS]; ROWS] {
+        self.back_buffer_written = true;
+        &mut self.back_buffer
+    }
+
+    /// Show the back buffer
+    ///
+    /// Swaps the two framebuffers and flushes only the cells that differ,
+    /// like [`Self::flush()`]. Afterwards the back buffer holds the previous
+    /// frame, so the next one has to be drawn completely.
+    ///
+    /// If [`Self::back_buffer_mut()`] wasn't called since the last present,
+    /// there's no new frame: nothing is swapped and this only flushes
+    /// pending changes of the front framebuffer. The generation only
+    /// increases if the new frame differs from the shown one.
+    #[cfg(feature = "double-buffer")]
+    pub fn present(&mut self) -> Result<(), Error<SPI::Error, DC::Error>> {
+        if self.back_buffer_written {
+            let mut changed = false;
+            for row in 0..ROWS {
+                for col in 0..COLS {
+                    if self.framebuffer[row][col] != self.back_buffer[row][col] {
+                        self.mark_dirty(row, col);
+                        changed = true;
+                    }
+                }
+            }
+            core::mem::swap(&mut self.framebuffer, &mut self.back_buffer);
+            self.back_buffer_written = false;
+            if changed {
+                self.generation += 1;
+            }
+        }
+        self.flush()
+    }
+
+    /// Whether the framebuffer changed since the last flush
+    ///
+    /// Writing a pixel with the color it already has doesn't count as a
+    /// change, so a render loop can skip [`Self::flush()`] on idle frames.
+    /// Cleared by flushing. Direct writes through [`Self::framebuffer_mut()`]
+    /// are only noticed after [`Self::mark_all_dirty()`].
+    pub fn needs_flush(&self) -> bool {
+        self.dirty.is_some()
+    }
+
+    /// Make the next flush send the whole framebuffer
+    ///
+    /// Also counts as a change for [`Self::generation()`].
+    pub fn mark_all_dirty(&mut self) {
+        #[cfg(feature = "row-hash")]
+        self.invalidate_row_hashes();
+        if !Self::HAS_FRAMEBUFFER {
+            return;
+        }
+        self.mark_dirty(0, 0);
+        self.mark_dirty(ROWS - 1, COLS - 1);
+        self.generation += 1;
+    }
+
+    /// Flush the framebuffer, but only if it changed since `last_seen`
+    ///
+    /// `last_seen` is the caller's copy of [`Self::generation()`] from the
+    /// last time it flushed. It is updated to the current generation when
+    /// flushing. Returns whether a flush happened.
+    pub fn flush_if_changed(
+        &mut self,
+        last_seen: &mut u64,
+    ) -> Result<bool, Error<SPI::Error, DC::Error>> {
+        if self.generation == *last_seen {
+            return Ok(false);
+        }
+        self.flush()?;
+        *last_seen = self.generation;
+        Ok(true)
+    }
+
+    /// Invert the pixels in a region of the framebuffer
+    ///
+    /// Flips every pixel inside of `area`, clamped to the display, for example
+    /// to highlight a selected menu item. Inverting the same region again
+    /// restores the original content. Cells that are completely inside of
+    /// `area` are flipped a byte at a time, only the cells on the edges go
+    /// pixel by pixel.
+    ///
+    /// To show it on the display, call [`Self::flush()`], or use
+    /// [`Self::invert_region_and_flush()`].
+    #[cfg(feature = "graphics")]
+    pub fn invert_region(&mut self, area: Rectangle) {
+        let area = area.intersection(&self.bounding_box());
+        if area.is_zero_sized() {
+            return;
+        }
+        let area = self.native_area(area);
+        let bottom_right = area.bottom_right().unwrap_or(area.top_left);
+        let (x0, y0) = (area.top_left.x as u16, area.top_left.y as u16);
+        let (x1, y1) = (bottom_right.x as u16, bottom_right.y as u16);
+
+        let px_per_col = self.px_per_col();
+        for row in (y0 / PX_PER_ROW)..=(y1 / PX_PER_ROW) {
+            let (cell_y0, cell_y1) = (row * PX_PER_ROW, row * PX_PER_ROW + PX_PER_ROW - 1);
+            for col in (x0 / px_per_col)..=(x1 / px_per_col) {
+                let (cell_x0, cell_x1) = (col * px_per_col, col * px_per_col + px_per_col - 1);
+                let (row, col) = (row as usize, col as usize);
+
+                if cell_x0 >= x0 && cell_x1 <= x1 && cell_y0 >= y0 && cell_y1 <= y1 {
+                    for byte in self.framebuffer[row][col].iter_mut() {
+                        *byte = !*byte;
+                    }
+                } else {
+                    for py in cell_y0.max(y0)..=cell_y1.min(y1) {
+                        for px in cell_x0.max(x0)..=cell_x1.min(x1) {
+                            let (_, _, byte, bitmask) = self.native_location(px, py);
+                            self.framebuffer[row][col][byte] ^= bitmask;
+                        }
+                    }
+                }
+                self.mark_dirty(row, col);
+            }
+        }
+        self.generation += 1;
+    }
+
+    /// Invert the pixels in a region and flush
+    ///
+    /// See [`Self::invert_region()`]. If nothing else changed since the last
+    /// flush, only the cells of the region are sent.
+    #[cfg(feature = "graphics")]
+    pub fn invert_region_and_flush(
+        &mut self,
+        area: Rectangle,
+    ) -> Result<(), Error<SPI::Error, DC::Error>> {
+        self.invert_region(area);
+        self.flush()
+    }
+
+    /// Draw a sprite with transparent pixels
+    ///
+    /// `bitmap` and `mask` are 1bpp images, `width` pixels wide, row-major
+    /// and MSB first, with each row padded to a full byte. A set bit in
+    /// `bitmap` is a black pixel. Only pixels whose bit is set in `mask` are
+    /// drawn, the others keep the content that was already there.
+    /// The sprite is clipped at the display edges.
+    ///
+    /// Returns an error if `mask` is smaller than `bitmap`.
+    /// To show it on the display, call [`Self::flush()`].
+    #[cfg(feature = "graphics")]
+    pub fn draw_sprite_masked(
+        &mut self,
+        bitmap: &[u8],
+        mask: &[u8],
+        width: u16,
+        origin: Point,
+    ) -> Result<(), Error<SPI::Error, DC::Error>> {
+        if width == 0 || mask.len() < bitmap.len() {
+            return Err(Error::BufferSize);
+        }
+        let stride = (width as usize).div_ceil(8);
+        let height = bitmap.len() / stride;
+
+        for sy in 0..height {
+            let y = origin.y + sy as i32;
+            if y < 0 || y >= self.height as i32 {
+                continue;
+            }
+            for sx in 0..width as usize {
+                let x = origin.x + sx as i32;
+                if x < 0 || x >= self.width as i32 {
+                    continue;
+                }
+                let index = sy * stride + sx / 8;
+                let bit = 0x80 >> (sx % 8);
+                if mask[index] & bit != 0 {
+                    self.write_pixel(x as u16, y as u16, bitmap[index] & bit != 0);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Runs commands to initialize the display.
+    ///
+    /// Only needs to be called once, afterwards use the setters to change
+    /// the configuration. Calling it again doesn't send anything and returns
+    /// [`Error::AlreadyInitialized`], because re-running the reset and init
+    /// sequence by accident glitches the display. To deliberately run it
+    /// again, call [`Self::reinit()`].
+    ///
+    /// Same as calling [`Self::hard_reset()`], [`Self::soft_reset()`],
+    /// [`Self::load_config()`] and [`Self::power_on()`] in that order.
+    /// With [`ST7306Builder::external_reset()`] the hard reset is skipped.
+    pub fn init<DELAY>(&mut self, delay: &mut DELAY) -> Result<(), Error<SPI::Error, DC::Error>>
+    where
+        DELAY: DelayMs<u8>,
+    {
+        if self.initialized {
+            return Err(Error::AlreadyInitialized);
+        }
+
+        // First do a hard reset because the controller might be in a bad state
+        // if the voltage was unstable in the beginning.
+        if !self.external_reset {
+            self.hard_reset(delay)?;
+        }
+        self.soft_reset(delay)?;
+        self.load_config()?;
+        self.power_on(delay)?;
+
+        self.initialized = true;
+        Ok(())
+    }
+
+    /// Program the NVM with new trim values
+    ///
+    /// **Dangerous.** Treat the NVM as one time programmable, like on other
+    /// Sitronix controllers: bits can't be cleared again. Wrong values
+    /// can leave the panel with voltages that damage it or show nothing at
+    /// all. Only do this for factory calibration, with values read back by
+    /// [`Self::nvm_read()`] from known good panels and checked against the
+    /// datasheet of your module.
+    ///
+    /// Writes `data` with NVMCTRL2 while programming is enabled by NVMPROM,
+    /// then waits for the programming to complete. Takes
+    /// [`NvmProgramConfirmation`] so it can't be called by accident.
+    /// The controller should be awake and in high power mode, with a stable
+    /// supply. Afterwards reset and init the controller to load the new
+    /// values.
+    pub fn nvm_program<DELAY>(
+        &mut self,
+        delay: &mut DELAY,
+        data: &[u8],
+        _confirmation: NvmProgramConfirmation,
+    ) -> Result<(), Error<SPI::Error, DC::Error>>
+    where
+        DELAY: DelayMs<u8>,
+    {
+        if data.is_empty() {
+            return Err(Error::BufferSize);
+        }
+        self.write_command(Instruction::NVMPROM, &[NVM_ENABLE])?;
+        let program = self.write_command(Instruction::NVMCTRL2, data);
+        if program.is_ok() {
+            delay.delay_ms(NVM_PROGRAM_MS);
+        }
+        // Disable programming again, even if writing failed
+        self.write_command(Instruction::NVMPROM, &[NVM_DISABLE])?;
+        program
+    }
+
+    /// Set the ID registers with ID1SET, ID2SET and ID3SET
+    ///
+    /// For example to mark modules with an ID during manufacturing, read
+    /// them back with [`Self::read_id()`]. The registers are volatile and
+    /// a reset loads them from the NVM again. To keep an ID across resets
+    /// it has to be programmed into the NVM, see [`Self::nvm_program()`]
+    /// and its warnings. Whether and where the IDs are stored in the NVM
+    /// depends on the module, check its datasheet.
+    pub fn set_id(
+        &mut self,
+        id1: u8,
+        id2: u8,
+        id3: u8,
+    ) -> Result<(), Error<SPI::Error, DC::Error>> {
+        self.write_command(Instruction::ID1SET, &[id1])?;
+        self.write_command(Instruction::ID2SET, &[id2])?;
+        self.write_command(Instruction::ID3SET, &[id3])
+    }
+
+    /// Reset the controller with the SWRESET command
+    ///
+    /// Like [`Self::hard_reset()`] this resets all registers to their
+    /// defaults and leaves the controller in sleep mode with the display off,
+    /// but it doesn't need the reset pin.
+    pub fn soft_reset<DELAY>(
+        &mut self,
+        delay: &mut DELAY,
+    ) -> Result<(), Error<SPI::Error, DC::Error>>
+    where
+        DELAY: DelayMs<u8>,
+    {
+        self.write_command(Instruction::SWRESET, &[])?;
+        delay_ms(delay, self.timings.soft_reset_ms);
+        self.reset_state();
+        Ok(())
+    }
+
+    /// Write the configuration registers
+    ///
+    /// Programs the voltages, timing and gate settings from the driver's
+    /// configuration. The reference init sequence sends these in sleep mode,
+    /// so after a reset and before [`Self::power_on()`].
+    pub fn load_config(&mut self) -> Result<(), Error<SPI::Error, DC::Error>> {
+        let commands = self.config_commands();
+        self.write_commands(&commands)
+    }
+
+    /// Commands of [`Self::load_config()`]
+    fn config_commands(&self) -> CommandList {
+        let mut commands = CommandList::new();
+
+        // NVM load control, see NvmLoadCtrl for the bits
+        commands.push(Instruction::NVMLOADCTRL, &self.nvm_load.as_bytes());
+        commands.push(Instruction::BSTEN, &[0x01]);
+
+        // Gate and source voltages, see VoltageConfig for the defaults
+        let voltage = self.voltage;
+        commands.push(Instruction::GCTRL, &voltage.gate);
+        commands.push(Instruction::VSHPCTRL, &voltage.vshp);
+        commands.push(Instruction::VSLPCTRL, &voltage.vslp);
+        commands.push(Instruction::VSHNCTRL, &voltage.vshn);
+        commands.push(Instruction::VSLNCTRL, &voltage.vsln);
+
+        // Gate Timing, only if configured
+        // Datasheet: 0x32, 0x03, 0x1F Reference code: not present
+        if let Some(params) = self.gate_timing {
+            commands.push(Instruction::GTCON, &params);
+        }
+
+        // Datasheet: 0x26, 0xE9, Reference: 0xA6, 0xE9 (HPM: 32Hz)
+        commands.push(Instruction::OSCSET, &self.tuning.oscillator);
+
+        // Frame Rate Control: 32Hz in High Power Mode, 1Hz in Low Power Mode
+        // Examples
+        // 0x12 = 0b10010 (32Hz in HPM, 1Hz in LPM)
+        // 0x15 = 0b10101 (32Hz in HPM, 8Hz in LPM)
+        commands.push(Instruction::FRCTRL, &[self.fps.as_u8()]);
+
+        // HPM EQ Control
+        commands.push(Instruction::GTUPEQH, &self.tuning.hpm_eq);
+        // LPM EQ Control
+        commands.push(Instruction::GTUPEQL, &self.tuning.lpm_eq);
+        // Source EQ Enable
+        commands.push(Instruction::SOUEQ, &[self.tuning.source_eq]);
+
+        // Gate Line Setting: 0x64 (100) lines for 400px, see PX_PER_GATE_LINE
+        commands.push(Instruction::GATESET, &[self.gate_lines]);
+
+        // First Gate Setting, only if configured
+        if let Some(line) = self.first_gate {
+            commands.push(Instruction::FSTCOM, &[line]);
+        }
+        commands
+    }
+
+    /// Wake the controller up and turn the display on
+    ///
+    /// Call after [`Self::load_config()`]. The data format, address window,
+    /// power mode and inversion are only written after sleep out, matching
+    /// the reference init sequence, so this also sends those.
+    pub fn power_on<DELAY>(&mut self, delay: &mut DELAY) -> Result<(), Error<SPI::Error, DC::Error>>
+    where
+        DELAY: DelayMs<u8>,
+    {
+        // Exit sleep mode
+        self.write_command(Instruction::SLPOUT, &[])?;
+        self.sleeping = false;
+        delay_ms(delay, self.timings.power_on_ms);
+
+        let commands = self.power_on_commands();
+        self.write_commands(&commands)?;
+        self.powered_on();
+        Ok(())
+    }
+
+    /// Commands of [`Self::power_on()`] after sleep out
+    fn power_on_commands(&self) -> CommandList {
+        let mut commands = CommandList::new();
+
+        // Ultra low power code (undocumented command)
+        commands.push(Instruction::LOWPOWER, &[0xC1, 0x4A, 0x26]);
+
+        // Source Voltage Select: VSHP1, VSLP1, VSHN1, VSLN1
+        commands.push(Instruction::VSHLSEL, &[0x00]);
+
+        // Memory Data Access Control, see MADCTL_DEFAULT
+        commands.push(Instruction::MADCTL, &[self.madctl]);
+
+        // Data Format: XDE=1 (3 bytes for 24 bits)
+        // BPS=1 packs 1 bit pixels for mono, BPS=0 2 bit pixels for 4 gray
+        // levels. Gamma Mode: Mono or 4 gray levels
+        match self.color_mode {
+            ColorMode::Mono => {
+                commands.push(Instruction::DTFORM, &[0x11]);
+                commands.push(Instruction::GAMAMS, &[0x20]);
+            }
+            ColorMode::Gray4 => {
+                commands.push(Instruction::DTFORM, &[0x10]);
+                commands.push(Instruction::GAMAMS, &[0x00]);
+            }
+        }
+
+        // Source Gamma Voltage, only if configured
+        if let Some(params) = self.gamma_voltage {
+            commands.push(Instruction::VSIKCTRL, &params);
+        }
+
+        // Panel Setting, the default is
+        //  01      = 1-Dot Inversion
+        //  || 10   = Frame Interval
+        //  || ||01 = One-Line Interface
+        //  || ||||
+        // 00101001 = 0x29
+        commands.push(Instruction::PNLSET, &[self.tuning.panel_setting()]);
+
+        // Column and row settings.
+        // Will be overridden by each pixel write
+        let window = self.addr_window;
+        commands.push(
+            Instruction::CASET,
+            &[window.col_start as u8, window.col_end as u8],
+        );
+        commands.push(
+            Instruction::RASET,
+            &[window.row_start as u8, window.row_end as u8],
+        );
+
+        // Enable auto power down
+        commands.push(
+            Instruction::AUTOPWRCTRL,
+            &[auto_power_down_param(self.autopowerdown)],
+        );
+
+        // Tearing enable on
+        if self.te_enable {
+            // 0x00 means V-blanking only
+            // 0x01 means V and H-blanking
+            commands.push(Instruction::TEON, &[0x00]);
+        } else {
+            commands.push(Instruction::TEOFF, &[]);
+        }
+
+        // Go into low power mode by default
+        commands.push(Instruction::LPM, &[]);
+
+        // Invert screen colors
+        if self.inverted {
+            commands.push(Instruction::INVON, &[]);
+        } else {
+            commands.push(Instruction::INVOFF, &[]);
+        }
+
+        commands.push(Instruction::DISPON, &[]);
+        commands
+    }
+
+    /// Track the state after the commands of [`Self::power_on()`]
+    fn powered_on(&mut self) {
+        self.ram_window = Some(self.addr_window);
+        self.autopowerdown_active = self.autopowerdown;
+        self.power_mode = PowerMode::Lpm;
+        self.display_on = true;
+        // The RAM content is unknown after the reset
+        self.mark_all_dirty();
+    }
+
+    /// Write a list of commands, in order
+    fn write_commands(
+        &mut self,
+        commands: &CommandList,
+    ) -> Result<(), Error<SPI::Error, DC::Error>> {
+        for (command, params) in commands.iter() {
+            self.write_command(command, params)?;
+        }
+        Ok(())
+    }
+
+    /// Reset and initialize the display again, even if it already was
+    pub fn reinit<DELAY>(&mut self, delay: &mut DELAY) -> Result<(), Error<SPI::Error, DC::Error>>
+    where
+        DELAY: DelayMs<u8>,
+    {
+        self.initialized = false;
+        self.init(delay)
+    }
+
+    /// Whether [`Self::init()`] has completed
+    pub fn is_initialized(&self) -> bool {
+        self.initialized
+    }
+
+    /// Whether the controller is in sleep mode
+    pub fn is_sleeping(&self) -> bool {
+        self.sleeping
+    }
+
+    /// Whether the display is on, see [`Self::on_off()`]
+    pub fn is_on(&self) -> bool {
+        self.display_on
+    }
+
+    /// Current power mode
+    pub fn power_mode(&self) -> PowerMode {
+        self.power_mode
+    }
+
+    /// Whether the colors are inverted
+    pub fn is_inverted(&self) -> bool {
+        self.inverted
+    }
+
+    /// Register values that were last written
+    ///
+    /// A software mirror for logging and debugging, the SPI is only used
+    /// for writing so nothing is read from the controller.
+    pub fn register_cache(&self) -> RegisterCache {
+        RegisterCache {
+            madctl: self.madctl,
+            frctrl: self.fps.as_u8(),
+            power_mode: self.power_mode,
+            inverted: self.inverted,
+            sleeping: self.sleeping,
+            display_on: self.display_on,
+            tearing: self.te_enable,
+            auto_power_down: self.autopowerdown_active,
+        }
+    }
+
+    /// Current frame rate configuration
+    pub fn fps(&self) -> FpsConfig {
+        self.fps
+    }
+
+    /// Duration of one frame in milliseconds, at the current power mode's
+    /// frame rate
+    pub fn frame_period_ms(&self) -> u16 {
+        self.fps.frame_interval_ms(self.power_mode)
+    }
+
+    /// Width and height in pixels, in the current orientation
+    pub fn dimensions(&self) -> (u16, u16) {
+        (self.width, self.height)
+    }
+
+    /// Turn the screen on or off
+    pub fn on_off(&mut self, on: bool) -> Result<(), Error<SPI::Error, DC::Error>> {
+        if on {
+            self.write_command(Instruction::DISPON, &[])?;
+        } else {
+            self.write_command(Instruction::DISPOFF, &[])?;
+        }
+        self.display_on = on;
+        Ok(())
+    }
+
+    /// Blank the panel but keep everything else, for an instant [`Self::wake()`]
+    ///
+    /// Only turns the display off with DISPOFF. The controller stays awake
+    /// in its current power mode and keeps the display RAM, so waking up
+    /// shows the same image again without flushing. The framebuffer and
+    /// what still needs to be flushed don't change either, drawing and
+    /// flushing still works while the panel is blank.
+    ///
+    /// Compared to the other ways of blanking:
+    /// - [`Self::sleep_in()`] also stops the booster and oscillator, which
+    ///   saves more power but takes longer to wake up from.
+    /// - [`Self::clear_ram()`] overwrites the RAM, so the image is gone.
+    pub fn standby(&mut self) -> Result<(), Error<SPI::Error, DC::Error>> {
+        self.on_off(false)
+    }
+
+    /// Show the RAM content again after [`Self::standby()`], with DISPON
+    pub fn wake(&mut self) -> Result<(), Error<SPI::Error, DC::Error>> {
+        self.on_off(true)
+    }
+
+    /// Have the display controller go into sleep mode
+    ///
+    /// Note: Must first go into HPM if currently in LPM, so after sleep_out,
+    /// if you want to be in LPM, need to manually go into LPM again.
+    pub fn sleep_in<DELAY>(&mut self, delay: &mut DELAY) -> Result<(), Error<SPI::Error, DC::Error>>
+    where
+        DELAY: DelayMs<u8>,
+    {
+        match self.power_mode {
+            PowerMode::Hpm => {
+                self.write_command(Instruction::SLPIN, &[])?;
+                delay_ms(delay, self.timings.sleep_in_ms);
+            }
+            PowerMode::Lpm => {
+                self.switch_mode(delay, PowerMode::Hpm)?;
+                delay_ms(delay, self.timings.lpm_exit_ms);
+                self.sleep_in(delay)?;
+            }
+        }
+        self.sleeping = true;
+        Ok(())
+    }
+
+    /// Wake the controller from sleep
+    pub fn sleep_out<DELAY>(
+        &mut self,
+        delay: &mut DELAY,
+    ) -> Result<(), Error<SPI::Error, DC::Error>>
+    where
+        DELAY: DelayMs<u8>,
+    {
+        self.write_command(Instruction::SLPOUT, &[])?;
+        delay_ms(delay, self.timings.sleep_out_ms);
+        self.sleeping = false;
+        Ok(())
+    }
+
+    /// Switch between high and low power mode
+    ///
+    /// Blocks for [`Timings::settle_frames`] frames at the new frame rate,
+    /// so the panel has refreshed at the new rate before it returns. With
+    /// the default of 2 frames that's 64ms when going to HPM at 32Hz, 2
+    /// seconds when going to LPM at the default 1Hz and up to 8 seconds at
+    /// [`LpmFps::Quarter`]. Set `settle_frames` to 0 to not wait at all.
+    ///
+    /// Nothing is waited for if the controller is already in `target_mode`.
+    /// [`Self::auto_power()`] and [`Self::enter_idle()`] switch without
+    /// waiting, the image stays on the panel during the switch.
+    pub fn switch_mode<DELAY>(
+        &mut self,
+        delay: &mut DELAY,
+        target_mode: PowerMode,
+    ) -> Result<(), Error<SPI::Error, DC::Error>>
+    where
+        DELAY: DelayMs<u8>,
+    {
+        if target_mode == self.power_mode {
+            return Ok(());
+        }
+        self.write_power_mode(target_mode)?;
+        delay_ms(
+            delay,
+            self.timings
+                .settle_frames
+                .saturating_mul(self.frame_period_ms()),
+        );
+        Ok(())
+    }
+
+    /// Send HPM or LPM without waiting for the panel to settle
+    fn write_power_mode(&mut self, mode: PowerMode) -> Result<(), Error<SPI::Error, DC::Error>> {
+        if mode == self.power_mode {
+            return Ok(());
+        }
+        match mode {
+            PowerMode::Hpm => self.write_command(Instruction::HPM, &[])?,
+            PowerMode::Lpm => self.write_command(Instruction::LPM, &[])?,
+        }
+        self.power_mode = mode;
+        Ok(())
+    }
+
+    /// Show the framebuffer with one fast refresh in high power mode
+    ///
+    /// For a UI that idles in low power mode and needs a quick, clean update
+    /// after user input. Switches to HPM, flushes, waits for the panel to
+    /// refresh a couple of times and then goes back to the previous power
+    /// mode. The frame rate configuration stays the same.
+    ///
+    /// Going back to LPM doesn't wait for it to settle, the new image is
+    /// already on the panel and stays there.
+    pub fn refresh_once<DELAY>(
+        &mut self,
+        delay: &mut DELAY,
+    ) -> Result<(), Error<SPI::Error, DC::Error>>
+    where
+        DELAY: DelayMs<u8>,
+    {
+        let previous = self.power_mode;
+        self.switch_mode(delay, PowerMode::Hpm)?;
+        self.flush()?;
+        delay_ms(
+            delay,
+            self.timings
+                .settle_frames
+                .saturating_mul(self.frame_period_ms()),
+        );
+        if previous == PowerMode::Lpm {
+            self.write_command(Instruction::LPM, &[])?;
+            self.power_mode = PowerMode::Lpm;
+        }
+        Ok(())
+    }
+
+    /// Switch between power modes depending on activity
+    ///
+    /// Call this once per frame, for example before every flush, with
+    /// whether something is animating that needs the fast refresh of high
+    /// power mode. Activity switches to HPM right away. Without activity the
+    /// display stays in HPM for some more calls before dropping back to LPM,
+    /// see [`Self::set_auto_power_timeout()`]. This hysteresis prevents
+    /// switching back and forth on every frame of an intermittent animation.
+    ///
+    /// Unlike [`Self::switch_mode()`] it never blocks: the next flush shows
+    /// up with the next frame at the new rate anyway.
+    pub fn auto_power(&mut self, activity: bool) -> Result<(), Error<SPI::Error, DC::Error>> {
+        if activity {
+            self.idle_calls = 0;
+            return self.write_power_mode(PowerMode::Hpm);
+        }
+        if self.power_mode == PowerMode::Hpm {
+            self.idle_calls = self.idle_calls.saturating_add(1);
+            if self.idle_calls >= self.auto_power_timeout {
+                self.write_power_mode(PowerMode::Lpm)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Set after how many calls without activity auto power goes to LPM
+    ///
+    /// There's no clock in the driver, so the timeout is counted in calls of
+    /// [`Self::auto_power()`], not in time. With one call per flush, it's the
+    /// number of idle flushes. Defaults to 10.
+    pub fn set_auto_power_timeout(&mut self, calls: u16) {
+        self.auto_power_timeout = calls;
+    }
+
+    /// Go into the lowest power state that still shows the image
+    ///
+    /// Wakes the controller if it's sleeping, turns the display on, enables
+    /// auto power down and switches to LPM, with the delays each step needs.
+    /// The panel then refreshes at the configured LPM frame rate, 1Hz by
+    /// default.
+    ///
+    /// Most of the current goes into driving the panel, so it scales roughly
+    /// with the frame rate: LPM at 1Hz draws a small fraction of HPM at 32Hz.
+    /// Auto power down additionally lets the controller turn off its
+    /// internal analog circuits between frames. Only [`Self::sleep_in()`]
+    /// draws less, but the image isn't refreshed while sleeping and fades.
+    ///
+    /// Flushing still works while idle, updates just show up on the next
+    /// slow frame. Only waking from sleep blocks, for
+    /// [`Timings::sleep_out_ms`], switching to LPM doesn't wait for slow
+    /// frames like [`Self::switch_mode()`] does. Undo with
+    /// [`Self::exit_idle()`].
+    pub fn enter_idle<DELAY>(
+        &mut self,
+        delay: &mut DELAY,
+    ) -> Result<(), Error<SPI::Error, DC::Error>>
+    where
+        DELAY: DelayMs<u8>,
+    {
+        if self.sleeping {
+            self.sleep_out(delay)?;
+        }
+        if !self.display_on {
+            self.on_off(true)?;
+        }
+        self.write_auto_power_down(true)?;
+        self.write_power_mode(PowerMode::Lpm)
+    }
+
+    /// Go back to HPM after [`Self::enter_idle()`]
+    ///
+    /// Also sets auto power down back to the configured value.
+    pub fn exit_idle<DELAY>(
+        &mut self,
+        delay: &mut DELAY,
+    ) -> Result<(), Error<SPI::Error, DC::Error>>
+    where
+        DELAY: DelayMs<u8>,
+    {
+        if self.sleeping {
+            self.sleep_out(delay)?;
+        }
+        self.switch_mode(delay, PowerMode::Hpm)?;
+        self.write_auto_power_down(self.autopowerdown)
+    }
+
+    /// Enable or disable auto power down (AUTOPWRCTRL)
+    fn write_auto_power_down(&mut self, enable: bool) -> Result<(), Error<SPI::Error, DC::Error>> {
+        self.write_command(Instruction::AUTOPWRCTRL, &[auto_power_down_param(enable)])?;
+        self.autopowerdown_active = enable;
+        Ok(())
+    }
+
+    /// Invert the colors on the screen
+    pub fn invert_screen(&mut self, inverted: bool) -> Result<(), Error<SPI::Error, DC::Error>> {
+        if inverted {
+            self.write_command(Instruction::INVON, &[])?;
+        } else {
+            self.write_command(Instruction::INVOFF, &[])?;
+        }
+        self.inverted = inverted;
+        Ok(())
+    }
+
+    /// Only drive a band of rows, blank the rest of the display
+    ///
+    /// Takes row addresses of 2 pixels each, relative to the display's RAM
+    /// window like [`Self::set_row_window()`], both inclusive. Returns an
+    /// error if the range is empty or goes beyond the controller's last row.
+    ///
+    /// Fewer gate lines are driven, so the panel uses less power. It adds
+    /// up with the savings of low power mode, which lowers the refresh rate:
+    /// a status bar that's only partially driven in LPM is the most power
+    /// efficient way to keep something on screen. The framebuffer and RAM
+    /// are unaffected, flushing still writes the whole display.
+    pub fn partial_on(
+        &mut self,
+        row_start: u16,
+        row_end: u16,
+    ) -> Result<(), Error<SPI::Error, DC::Error>> {
+        let row_start = self.addr_window.row_start + row_start;
+        let row_end = self.addr_window.row_start + row_end;
+        if row_start > row_end || row_end > ROW_MAX {
+            return Err(Error::OutOfBounds);
+        }
+        self.write_command(Instruction::PTLAR, &[row_start as u8, row_end as u8])?;
+        self.write_command(Instruction::PTLON, &[])
+    }
+
+    /// Drive the whole display again, after [`Self::partial_on()`]
+    pub fn partial_off(&mut self) -> Result<(), Error<SPI::Error, DC::Error>> {
+        self.write_command(Instruction::PTLOFF, &[])
+    }
+
+    /// Scroll the display vertically
+    ///
+    /// The display starts showing the RAM from row address `line` and wraps
+    /// around at the end, so content moves up by `line` rows of 2 pixels.
+    /// There's no command to limit scrolling to a part of the display, the
+    /// whole RAM scrolls. `line` is clamped to the last row address.
+    ///
+    /// Scrolling only changes where the RAM is shown, not the framebuffer
+    /// coordinates. A pixel drawn at `y` stays at `y` in the framebuffer and
+    /// RAM, and shows up `2 * line` pixels higher on the screen, wrapping
+    /// around at the top. To append a line of text to a scrolling log, draw
+    /// it into the rows that just scrolled out at the top, then scroll by
+    /// one more line.
+    pub fn set_scroll_start(&mut self, line: u16) -> Result<(), Error<SPI::Error, DC::Error>> {
+        let line = line.min(ROW_MAX);
+        self.write_command(Instruction::VSCSAD, &[line as u8])
+    }
+
+    /// Set the first gate line that the panel is scanned from (FSTCOM)
+    ///
+    /// Three settings decide where the image ends up on the glass. GATESET
+    /// sets how many gate lines are driven, see
+    /// [`ST7306Builder::gate_lines()`]. FSTCOM sets which gate line is
+    /// driven first, so RAM row 0 shows up at that line and the whole image
+    /// moves down with a higher value. The row offset of
+    /// [`ST7306Builder::offset()`] doesn't move anything on the glass, it
+    /// only selects which RAM rows the driver writes to.
+    ///
+    /// If the image is shifted vertically on a panel, adjust the first gate
+    /// until it lines up, then configure it with
+    /// [`ST7306Builder::first_gate()`] so init sends it.
+    pub fn set_first_gate(&mut self, line: u8) -> Result<(), Error<SPI::Error, DC::Error>> {
+        self.write_command(Instruction::FSTCOM, &[line])?;
+        self.first_gate = Some(line);
+        Ok(())
+    }
+
+    /// Set the gate timing (GTCON)
+    ///
+    /// Also kept for the next init, see [`ST7306Builder::gate_timing()`].
+    pub fn set_gate_timing(&mut self, params: [u8; 3]) -> Result<(), Error<SPI::Error, DC::Error>> {
+        self.write_command(Instruction::GTCON, &params)?;
+        self.gate_timing = Some(params);
+        Ok(())
+    }
+
+    /// Set the source gamma voltages (VSIKCTRL)
+    ///
+    /// They set the source voltages the gray levels are driven with, so they
+    /// mostly matter in [`ColorMode::Gray4`]. In mono mode GAMAMS selects
+    /// the two level gamma and the panel only uses the black and white
+    /// voltages. Use them to match the gray levels to a panel.
+    ///
+    /// Also kept for the next init, see [`ST7306Builder::gamma_voltage()`].
+    pub fn set_gamma_voltage(
+        &mut self,
+        params: [u8; GAMMA_VOLTAGE_LEN],
+    ) -> Result<(), Error<SPI::Error, DC::Error>> {
+        self.write_command(Instruction::VSIKCTRL, &params)?;
+        self.gamma_voltage = Some(params);
+        Ok(())
+    }
+
+    /// Set the gate line at which the tearing effect (TE) signal goes high
+    ///
+    /// By default the TE signal rises at the start of the vertical blanking
+    /// period, see [`Self::wait_for_te()`]. With a scanline set, it rises
+    /// when the controller starts scanning that gate line instead, so
+    /// [`Self::wait_for_te()`] and [`Self::flush_synced()`] return right
+    /// after the scan passed it. Writing the rows above it then can't tear,
+    /// as long as the write finishes before the next frame reaches them.
+    /// The line is counted like [`ST7306Builder::gate_lines()`].
+    ///
+    /// Only has an effect with [`ST7306Builder::tearing()`] enabled, which
+    /// turns on TEON in V-blanking mode. Returns [`Error::OutOfBounds`] if
+    /// `line` is beyond the gate lines configured with GATESET.
+    pub fn set_tear_scanline(&mut self, line: u16) -> Result<(), Error<SPI::Error, DC::Error>> {
+        if line >= self.gate_lines as u16 {
+            return Err(Error::OutOfBounds);
+        }
+        self.write_command(Instruction::TESCAN, &[line as u8])?;
+        self.tear_scanline = line;
+        Ok(())
+    }
+
+    /// Gate line at which the TE signal goes high, see
+    /// [`Self::set_tear_scanline()`]
+    pub fn tear_scanline(&self) -> u16 {
+        self.tear_scanline
+    }
+
+    /// Set the Data Order (DO) bit of MADCTL
+    ///
+    /// It controls the order in which the controller puts the bits of each
+    /// byte onto the pixels. The default is `true`. Depending on how the
+    /// panel is routed to the controller, it might need to be flipped. If it
+    /// is wrong, the pixels within each 12 pixel column end up in the wrong
+    /// order, which usually looks like neighbouring pixel columns swapped.
+    /// Try both settings when bringing up a new panel.
+    pub fn set_data_order(&mut self, data_order: bool) -> Result<(), Error<SPI::Error, DC::Error>> {
+        if data_order {
+            self.madctl |= MADCTL_DO;
+        } else {
+            self.madctl &= !MADCTL_DO;
+        }
+        self.write_command(Instruction::MADCTL, &[self.madctl])
+    }
+
+    /// Set the MADCTL register
+    ///
+    /// Gives control over the individual mirror and flip bits, for example
+    /// for a panel that is mounted upside down. Unlike [`Self::set_orientation()`]
+    /// this is done by the controller and mirrors whole RAM addresses, which
+    /// are cells of 12x2 pixels. Changing [`MadctlFlags::mx`] or
+    /// [`MadctlFlags::my`] also mirrors the pixels inside of the cells, see
+    /// [`Self::set_mirror_x()`]. Setting [`MadctlFlags::mv`] swaps how the
+    /// controller steps through the address window, which the flush doesn't
+    /// account for, so the picture ends up scrambled.
+    ///
+    /// Kept until the next [`Self::set_madctl()`] or [`Self::set_data_order()`],
+    /// also across [`Self::init()`].
+    pub fn set_madctl(&mut self, flags: MadctlFlags) -> Result<(), Error<SPI::Error, DC::Error>> {
+        self.update_madctl(flags.bits())
+    }
+
+    /// Current MADCTL bits, see [`Self::set_madctl()`]
+    pub fn madctl(&self) -> MadctlFlags {
+        MadctlFlags::from_bits(self.madctl)
+    }
+
+    /// Mirror the display horizontally
+    ///
+    /// Toggles the MX bit of MADCTL with a single command, without going
+    /// through [`Self::init()`], for example when the device is turned
+    /// around in its dock. The controller only mirrors whole cells of 12x2
+    /// pixels, so the driver mirrors the pixels inside of each cell, both the
+    /// ones already in the framebuffer and the ones drawn later. Pixel
+    /// coordinates stay the same. Until the next [`Self::flush()`], which
+    /// sends the whole framebuffer, the panel shows the old picture with
+    /// its cells mirrored.
+    ///
+    /// The controller mirrors across all of its RAM columns. If the panel
+    /// doesn't start at column 0, the picture also moves sideways, use
+    /// [`Self::set_column_window()`] to move it back.
+    pub fn set_mirror_x(&mut self, mirror: bool) -> Result<(), Error<SPI::Error, DC::Error>> {
+        let mx = MADCTL_DEFAULT & MadctlFlags::MX;
+        let bit = if mirror { mx ^ MadctlFlags::MX } else { mx };
+        self.update_madctl((self.madctl & !MadctlFlags::MX) | bit)
+    }
+
+    /// Flip the display upside down
+    ///
+    /// Like [`Self::set_mirror_x()`], but toggles the MY bit and mirrors the
+    /// two pixel rows of each cell.
+    pub fn set_mirror_y(&mut self, mirror: bool) -> Result<(), Error<SPI::Error, DC::Error>> {
+        let my = MADCTL_DEFAULT & MadctlFlags::MY;
+        let bit = if mirror { my ^ MadctlFlags::MY } else { my };
+        self.update_madctl((self.madctl & !MadctlFlags::MY) | bit)
+    }
+
+    /// Whether the display is mirrored horizontally and vertically, see
+    /// [`Self::set_mirror_x()`] and [`Self::set_mirror_y()`]
+    pub fn mirrored(&self) -> (bool, bool) {
+        let flags = MadctlFlags::from_bits(self.madctl ^ MADCTL_DEFAULT);
+        (flags.mx, flags.my)
+    }
+
+    /// Write MADCTL and mirror the cells of the framebuffer to match the
+    /// new MX and MY bits
+    fn update_madctl(&mut self, madctl: u8) -> Result<(), Error<SPI::Error, DC::Error>> {
+        let changed = MadctlFlags::from_bits(self.madctl ^ madctl);
+        if changed.mx || changed.my {
+            let bits = match self.color_mode {
+                ColorMode::Mono => 1,
+                ColorMode::Gray4 => 2,
+            };
+            for cell in self.framebuffer.as_flattened_mut() {
+                *cell = mirror_cell(*cell, bits, changed.mx, changed.my);
+            }
+            self.mark_all_dirty();
+        }
+        self.madctl = madctl;
+        self.write_command(Instruction::MADCTL, &[self.madctl])
+    }
+
+    /// Set the brightness of the image, from 0 to 255
+    ///
+    /// The panel is reflective and has no backlight, so this changes how
+    /// strongly the pixels are driven. Higher levels make black pixels darker
+    /// and increase the contrast. It adjusts the positive and negative source
+    /// high voltages (VSHP/VSHN) together, by up to 0.12V in either direction
+    /// of the configured [`VoltageConfig`], which is used at level 128. The
+    /// range is kept small so the panel isn't driven far from those voltages.
+    ///
+    /// VSHP is also moved by [`Self::set_contrast()`], both levels are kept
+    /// and added up, so setting one doesn't undo the other.
+    ///
+    /// The setting is volatile, [`Self::reinit()`] restores the configured
+    /// voltages. To keep a level across power cycles, store it in the
+    /// application and set it again after init. Persisting it in the NVM
+    /// isn't supported: the NVM is one time programmable and where it keeps
+    /// the source voltages depends on the module, so a wrong write could
+    /// leave the panel unusable. See [`Self::nvm_program()`] for factory
+    /// calibration.
+    pub fn set_brightness(&mut self, level: u8) -> Result<(), Error<SPI::Error, DC::Error>> {
+        self.brightness = level;
+        let vshn = shift_voltage(self.voltage.vshn, level_to_delta(level));
+        self.write_command(Instruction::VSHPCTRL, &self.vshp_levels())?;
+        self.write_command(Instruction::VSHNCTRL, &vshn)
+    }
+
+    /// Set the contrast of the image, from 0 to 255
+    ///
+    /// Moves the positive source high (VSHP) and low (VSLP) voltages apart
+    /// for higher levels and closer together for lower ones, which helps
+    /// against a washed out image in bright light. Each moves by up to 0.12V
+    /// in either direction of the configured [`VoltageConfig`], which is
+    /// used at level 128. That range keeps the panel within the voltages of
+    /// the reference init code, larger steps would need a custom
+    /// [`VoltageConfig`] that was checked against the panel's datasheet.
+    ///
+    /// Can be called at runtime, for example from an ambient light sensor.
+    /// VSHP includes the level of [`Self::set_brightness()`] as well.
+    /// The setting is volatile, [`Self::reinit()`] restores the configured
+    /// voltages.
+    pub fn set_contrast(&mut self, level: u8) -> Result<(), Error<SPI::Error, DC::Error>> {
+        self.contrast = level;
+        let vslp = shift_voltage(self.voltage.vslp, -level_to_delta(level));
+        self.write_command(Instruction::VSHPCTRL, &self.vshp_levels())?;
+        self.write_command(Instruction::VSLPCTRL, &vslp)
+    }
+
+    /// VSHP codes for the current brightness and contrast levels
+    fn vshp_levels(&self) -> [u8; 4] {
+        let delta = level_to_delta(self.brightness) + level_to_delta(self.contrast);
+        shift_voltage(self.voltage.vshp, delta)
+    }
+
+    /// Change the FPS config
+    ///
+    /// Note that to change to the desired FPS, you might have to switch between
+    /// low and high power modes.
+    pub fn set_fps(&mut self, fps: FpsConfig) -> Result<(), Error<SPI::Error, DC::Error>> {
+        self.fps = fps;
+        self.write_command(Instruction::FRCTRL, &[self.fps.as_u8()])?;
+        Ok(())
+    }
+
+    /// Set the slowest frame rate of a power mode that refreshes at least
+    /// `hz` times per second
+    ///
+    /// Keeps the rate of the other power mode. Returns an error if no rate
+    /// is fast enough, see [`LpmFps::for_min_hz()`] and
+    /// [`HpmFps::for_min_hz()`].
+    pub fn set_fps_for_min_hz(
+        &mut self,
+        mode: PowerMode,
+        hz: f32,
+    ) -> Result<(), Error<SPI::Error, DC::Error>> {
+        let mut fps = self.fps;
+        match mode {
+            PowerMode::Hpm => fps.hpm = HpmFps::for_min_hz(hz).ok_or(Error::OutOfBounds)?,
+            PowerMode::Lpm => fps.lpm = LpmFps::for_min_hz(hz).ok_or(Error::OutOfBounds)?,
+        }
+        self.set_fps(fps)
+    }
+
+    /// Change the analog tuning values
+    ///
+    /// Nothing is written to the controller, the values take effect with
+    /// [`Self::reinit()`]. Use the setters of the single values to change
+    /// them at runtime. Returns [`ConfigError::InvalidFrameInterval`] and
+    /// keeps the current values if the frame interval is bigger than 3.
+    pub fn set_tuning(&mut self, tuning: TuningConfig) -> Result<(), ConfigError> {
+        if tuning.frame_interval > 0b11 {
+            return Err(ConfigError::InvalidFrameInterval);
+        }
+        self.tuning = tuning;
+        Ok(())
+    }
+
+    /// Change the source EQ
+    ///
+    /// See [`TuningConfig::source_eq`].
+    pub fn set_source_eq(&mut self, value: u8) -> Result<(), Error<SPI::Error, DC::Error>> {
+        self.tuning.source_eq = value;
+        self.write_command(Instruction::SOUEQ, &[value])
+    }
+
+    /// Change the update period gate EQ in high power mode
+    ///
+    /// See [`TuningConfig::hpm_eq`].
+    pub fn set_hpm_eq(&mut self, params: [u8; 10]) -> Result<(), Error<SPI::Error, DC::Error>> {
+        self.tuning.hpm_eq = params;
+        self.write_command(Instruction::GTUPEQH, &params)
+    }
+
+    /// Change the update period gate EQ in low power mode
+    ///
+    /// See [`TuningConfig::lpm_eq`].
+    pub fn set_lpm_eq(&mut self, params: [u8; 8]) -> Result<(), Error<SPI::Error, DC::Error>> {
+        self.tuning.lpm_eq = params;
+        self.write_command(Instruction::GTUPEQL, &params)
+    }
+
+    /// Change the frame interval
+    ///
+    /// See [`TuningConfig::frame_interval`]. Returns an error if the interval
+    /// is bigger than 3.
+    pub fn set_frame_interval(&mut self, interval: u8) -> Result<(), Error<SPI::Error, DC::Error>> {
+        if interval > 0b11 {
+            return Err(Error::OutOfBounds);
+        }
+        self.tuning.frame_interval = interval;
+        self.write_command(Instruction::PNLSET, &[self.tuning.panel_setting()])
+    }
+
+    /// Change the inversion scheme
+    ///
+    /// See [`TuningConfig::inversion`].
+    pub fn set_inversion(
+        &mut self,
+        inversion: Inversion,
+    ) -> Result<(), Error<SPI::Error, DC::Error>> {
+        self.tuning.inversion = inversion;
+        self.write_command(Instruction::PNLSET, &[self.tuning.panel_setting()])
+    }
+
+    /// Enable or disable the extended command set with EXTBCTRL
+    ///
+    /// The reference init sequence never sends EXTBCTRL and its gate and
+    /// source voltage writes (GCTRL, VSHPCTRL, VSLPCTRL, VSHNCTRL and
+    /// VSLNCTRL) take effect, so [`Self::init()`] doesn't enable it either.
+    /// If writes to a register are ignored on a module, check its datasheet
+    /// for whether that register needs the extended command set, then
+    /// enable it before the write.
+    pub fn enable_extended_commands(
+        &mut self,
+        enable: bool,
+    ) -> Result<(), Error<SPI::Error, DC::Error>> {
+        let param = if enable { EXTB_ENABLE } else { EXTB_DISABLE };
+        self.write_command(Instruction::EXTBCTRL, &[param])
+    }
+
+    /// Change the oscillator setting
+    ///
+    /// See [`TuningConfig::oscillator`].
+    pub fn set_oscillator(&mut self, params: [u8; 2]) -> Result<(), Error<SPI::Error, DC::Error>> {
+        self.tuning.oscillator = params;
+        self.write_command(Instruction::OSCSET, &params)
+    }
+
+    /// Hard reset the controller by toggling the reset pin
+    ///
+    /// Afterwards the controller is in sleep mode with the display off and
+    /// needs [`Self::load_config()`] and [`Self::power_on()`] again.
+    pub fn hard_reset<DELAY>(
+        &mut self,
+        delay: &mut DELAY,
+    ) -> Result<(), Error<SPI::Error, DC::Error>>
+    where
+        DELAY: DelayMs<u8>,
+    {
+        self.rst.set_high().map_err(Error::Pin)?;
+        delay_ms(delay, self.timings.reset_ms);
+
+        self.rst.set_low().map_err(Error::Pin)?;
+        delay_ms(delay, self.timings.reset_ms);
+
+        self.rst.set_high().map_err(Error::Pin)?;
+        self.reset_state();
+        Ok(())
+    }
+
+    /// Track the state of the controller after a reset
+    fn reset_state(&mut self) {
+        self.sleeping = true;
+        self.display_on = false;
+        self.power_mode = PowerMode::Hpm;
+        self.ram_window = None;
+        self.tear_scanline = 0;
+        self.step_row = None;
+        self.brightness = LEVEL_DEFAULT;
+        self.contrast = LEVEL_DEFAULT;
+    }
+
+    /// Write a command with optional parameters
+    ///
+    /// This function makes sure CS and DC pins are set correctly
+    pub fn write_command(
+        &mut self,
+        command: Instruction,
+        params: &[u8],
+    ) -> Result<(), Error<SPI::Error, DC::Error>> {
+        self.cs.set_low().map_err(Error::Pin)?;
+        self.dc.set_low().map_err(Error::Pin)?;
+        self.spi.write(&[command as u8]).map_err(Error::Spi)?;
+        if !params.is_empty() {
+            self.start_data()?;
+            self.write_command_data(params)?;
+        }
+        self.cs.set_high().map_err(Error::Pin)?;
+        Ok(())
+    }
+
+    /// Before writing data, the CS and DC pins must be set correctly
+    ///
+    /// This command can be used if you want to write extra data, in addition
+    /// to a command's parameters.
+    pub fn start_data(&mut self) -> Result<(), Error<SPI::Error, DC::Error>> {
+        self.cs.set_low().map_err(Error::Pin)?;
+        self.dc.set_high().map_err(Error::Pin)
+    }
+
+    /// Write data that's part of a command
+    ///
+    /// Either the command ID or the parameters.
+    fn write_command_data(&mut self, data: &[u8]) -> Result<(), Error<SPI::Error, DC::Error>> {
+        self.spi.write(data).map_err(Error::Spi)
+    }
+
+    /// Write to the display controller's RAM
+    ///
+    /// The caller must first send a [`Instruction::RAMWR`] and can then call this
+    /// function repeatedly to fill the entire memory window.
+    ///
+    /// Must always write to RAM in 24 bit sequences, so the length of `data`
+    /// must be a multiple of 3. It's sent in a single SPI write, unless it's
+    /// longer than [`ST7306Builder::max_transfer()`].
+    pub fn write_ram(&mut self, data: &[u8]) -> Result<(), Error<SPI::Error, DC::Error>> {
+        if !data.len().is_multiple_of(3) {
+            return Err(Error::BufferSize);
+        }
+        write_chunked(&mut self.spi, data, self.max_transfer).map_err(Error::Spi)
+    }
+
+    /// Continue writing to RAM where the last write stopped
+    ///
+    /// Sends WRMEMC and then `data`, which must be a multiple of 3 bytes like
+    /// with [`Self::write_ram()`]. Unlike [`Instruction::RAMWR`], which starts
+    /// at the beginning of the address window, the controller resumes at its
+    /// current RAM pointer. That allows splitting a window into several
+    /// writes, with other commands in between.
+    ///
+    /// To rewrite the same region again, for example a blinking cursor,
+    /// there's no need to send the window again either. A plain RAMWR starts
+    /// at the beginning of the window that's still set.
+    pub fn write_ram_continue(&mut self, data: &[u8]) -> Result<(), Error<SPI::Error, DC::Error>> {
+        if !data.len().is_multiple_of(3) {
+            return Err(Error::BufferSize);
+        }
+        self.write_command(Instruction::WRMEMC, &[])?;
+        self.start_data()?;
+        write_chunked(&mut self.spi, data, self.max_transfer).map_err(Error::Spi)
+    }
+
+    /// Write cells from an iterator to the display controller's RAM
+    ///
+    /// Like [`Self::write_ram()`], but the cells can be generated on the fly
+    /// instead of coming from a slice. Each item is the three bytes of one
+    /// cell. They're collected into a small buffer on the stack and sent in
+    /// batches of 16 cells.
+    pub fn write_ram_iter<I>(&mut self, iter: I) -> Result<(), Error<SPI::Error, DC::Error>>
+    where
+        I: IntoIterator<Item = (u8, u8, u8)>,
+    {
+        let mut buf = [0; ITER_CHUNK_CELLS * 3];
+        let mut len = 0;
+        for (a, b, c) in iter {
+            buf[len..len + 3].copy_from_slice(&[a, b, c]);
+            len += 3;
+            if len == buf.len() {
+                self.spi.write(&buf).map_err(Error::Spi)?;
+                len = 0;
+            }
+        }
+        if len > 0 {
+            self.spi.write(&buf[..len]).map_err(Error::Spi)?;
+        }
+        Ok(())
+    }
+
+    /// Write a whole frame of packed pixels to RAM
+    ///
+    /// Sets the full address window and sends `data` straight to the
+    /// controller, without copying it into the framebuffer. `data` is packed
+    /// like [`Self::framebuffer()`], for the whole configured display, so
+    /// it must be 3 bytes for every column and row of the address window.
+    /// Otherwise [`Error::BufferSize`] is returned and nothing is sent.
+    ///
+    /// Meant for [`ST7306Streaming`], which has no framebuffer. With a
+    /// framebuffer, the RAM no longer matches it afterwards.
+    pub fn write_frame(&mut self, data: &[u8]) -> Result<(), Error<SPI::Error, DC::Error>> {
+        let (cols, rows) = self.window_cells();
+        if data.len() != cols as usize * rows as usize * 3 {
+            return Err(Error::BufferSize);
+        }
+        self.write_full_window()?;
+        self.write_command(Instruction::RAMWR, &[])?;
+        self.start_data()?;
+        write_chunked(&mut self.spi, data, self.max_transfer).map_err(Error::Spi)
+    }
+
+    /// Number of columns and rows in the RAM address window
+    fn window_cells(&self) -> (u16, u16) {
+        let window = self.addr_window;
+        (
+            window.col_end - window.col_start + 1,
+            window.row_end - window.row_start + 1,
+        )
+    }
+
+    /// Clear the controller's RAM
+    ///
+    /// Basically turns the screen all white. The display is turned off while
+    /// clearing, so the screen blanks for a moment. To clear without that,
+    /// use [`Self::clear_white_no_blank()`].
+    pub fn clear_ram(&mut self) -> Result<(), Error<SPI::Error, DC::Error>> {
+        self.clear_ram_to(false)
+    }
+
+    /// Fill the controller's RAM with black (`true`) or white (`false`)
+    ///
+    /// Like [`Self::clear_ram()`], but with either color. Much faster than
+    /// flushing a filled framebuffer, because no pixel data is sent.
+    pub fn clear_ram_to(&mut self, black: bool) -> Result<(), Error<SPI::Error, DC::Error>> {
+        self.on_off(false)?;
+        self.clear_ram_cmd(true, black)?;
+        self.on_off(true)?;
+        Ok(())
+    }
+
+    /// Clear the controller's RAM without turning the display off
+    ///
+    /// Unlike [`Self::clear_ram()`] this doesn't send DISPOFF/DISPON, so the
+    /// screen doesn't go blank before clearing. The reference sequence turns
+    /// the display off while clearing, because the panel might show the
+    /// clear in progress. If that is visible on your panel, clear the
+    /// framebuffer and [`Self::flush()`] instead, which never blanks.
+    pub fn clear_ram_keep_on(&mut self) -> Result<(), Error<SPI::Error, DC::Error>> {
+        self.clear_ram_cmd(true, false)
+    }
+
+    /// Turn the screen all white without blanking it
+    ///
+    /// Clears the framebuffer and writes it to RAM in a single flush, instead
+    /// of using the CLRAM command. The display stays on and the panel picks
+    /// up the white frame on its next refresh, like any other flush, so
+    /// there's no flicker and no partially cleared frame. It takes longer
+    /// than [`Self::clear_ram_keep_on()`] because the whole RAM is sent over
+    /// SPI.
+    pub fn clear_white_no_blank(&mut self) -> Result<(), Error<SPI::Error, DC::Error>> {
+        self.clear_buffer(false);
+        self.flush_full()
+    }
+
+    /// Low level command, don't use if you don't know what you're doing
+    ///
+    /// Before calling this, must call [`Self::on_off()`]. `black` selects
+    /// what the RAM is filled with when clearing.
+    pub fn clear_ram_cmd(
+        &mut self,
+        clear: bool,
+        black: bool,
+    ) -> Result<(), Error<SPI::Error, DC::Error>> {
+        let white_mask = 0b01000000;
+        let byte = if black {
+            0b00001111
+        } else {
+            0b00001111 | white_mask
+        };
+        let enable_clear_mask = 0b10000000;
+
+        if clear {
+            self.write_command(Instruction::CLRAM, &[byte + enable_clear_mask])?;
+            // The RAM no longer matches the framebuffer
+            self.mark_all_dirty();
+        } else {
+            // TODO: I don't know when there's a need to do this
+            self.write_command(Instruction::CLRAM, &[byte])?;
+        }
+
+        Ok(())
+    }
+
+    /// Rotate the display content
+    ///
+    /// In landscape orientations width and height are swapped, so
+    /// [`OriginDimensions::size()`] and all pixel coordinates follow the
+    /// rotated display. Pixels that are already in the framebuffer stay
+    /// where they are on the panel. Call it before drawing the new content.
+    ///
+    /// The rotation is done in software, when writing to the framebuffer.
+    /// MADCTL could only swap and mirror the RAM addresses, but each address
+    /// is a cell of 12x2 pixels, so a swap wouldn't rotate the pixels inside
+    /// of the cells. The framebuffer, the RAM address window and the bytes
+    /// sent when flushing stay in the panel's native orientation.
+    pub fn set_orientation(
+        &mut self,
+        orientation: &Orientation,
+    ) -> Result<(), Error<SPI::Error, DC::Error>> {
+        let (width, height) = self.native_size();
+        self.orientation = *orientation;
+        (self.width, self.height) = match orientation {
+            Orientation::Portrait | Orientation::PortraitSwapped => (width, height),
+            Orientation::Landscape | Orientation::LandscapeSwapped => (height, width),
+        };
+        Ok(())
+    }
+
+    /// Current orientation, see [`Self::set_orientation()`]
+    pub fn orientation(&self) -> Orientation {
+        self.orientation
+    }
+
+    /// Sets a pixel color at the given coords.
+    ///
+    /// `color` is the brightness, from 0 (black) to 255 (white). Below 128
+    /// the pixel is black, like with the [`DrawTarget`] impl. In the
+    /// framebuffer a set bit is a black pixel, [`Self::invert_screen()`]
+    /// only flips how the panel shows it.
+    ///
+    /// Changes the pixel value in the framebuffer at the bit where the
+    /// display controller expects it.
+    ///
+    /// Returns an error if the coords are outside of the display.
+    /// To show it on the display, call [`Self::flush()`].
+    pub fn set_pixel(
+        &mut self,
+        x: u16,
+        y: u16,
+        color: u8,
+    ) -> Result<(), Error<SPI::Error, DC::Error>> {
+        if x >= self.width || y >= self.height {
+            return Err(Error::OutOfBounds);
+        }
+        self.write_pixel(x, y, color < BLACK_THRESHOLD);
+        Ok(())
+    }
+
+    /// Turn a pixel on (black) or off (white)
+    ///
+    /// Returns an error if the coords are outside of the display.
+    /// To show it on the display, call [`Self::flush()`].
+    pub fn set_pixel_on(
+        &mut self,
+        x: u16,
+        y: u16,
+        on: bool,
+    ) -> Result<(), Error<SPI::Error, DC::Error>> {
+        if x >= self.width || y >= self.height {
+            return Err(Error::OutOfBounds);
+        }
+        self.write_pixel(x, y, on);
+        Ok(())
+    }
+
+    /// Turn many pixels on (black) or off (white)
+    ///
+    /// Takes `(x, y, on)` tuples, coords outside of the display are skipped.
+    /// Optionally flushes once after setting all of them.
+    pub fn set_pixels<I>(
+        &mut self,
+        points: I,
+        flush: bool,
+    ) -> Result<(), Error<SPI::Error, DC::Error>>
+    where
+        I: IntoIterator<Item = (u16, u16, bool)>,
+    {
+        for (x, y, on) in points {
+            if x < self.width && y < self.height {
+                self.write_pixel(x, y, on);
+            }
+        }
+        if flush {
+            self.flush()?;
+        }
+        Ok(())
+    }
+
+    /// Turn all pixels in a rectangle on (black) or off (white)
+    ///
+    /// The rectangle starts at `x`, `y` and is clamped to the display.
+    /// Cells of 12x2 pixels that are completely inside of it are filled a
+    /// byte at a time, so large rectangles are fast.
+    /// To show it on the display, call [`Self::flush()`].
+    pub fn fill_rect(&mut self, x: u16, y: u16, width: u16, height: u16, on: bool) {
+        let x_end = x.saturating_add(width).min(self.width);
+        let y_end = y.saturating_add(height).min(self.height);
+        if x >= x_end || y >= y_end {
+            return;
+        }
+        let (x0, y0) = self.native_point(x, y);
+        let (x1, y1) = self.native_point(x_end - 1, y_end - 1);
+        self.fill_native_rect(x0.min(x1), y0.min(y1), x0.max(x1), y0.max(y1), on);
+    }
+
+    /// Fill a rectangle, inclusive and in the panel's native orientation
+    fn fill_native_rect(&mut self, x0: u16, y0: u16, x1: u16, y1: u16, on: bool) {
+        let px_per_col = self.px_per_col();
+        let cell = [if on { 0xFF } else { 0x00 }; 3];
+        for row in (y0 / PX_PER_ROW)..=(y1 / PX_PER_ROW) {
+            let (cell_y0, cell_y1) = (row * PX_PER_ROW, row * PX_PER_ROW + PX_PER_ROW - 1);
+            for col in (x0 / px_per_col)..=(x1 / px_per_col) {
+                let (cell_x0, cell_x1) = (col * px_per_col, col * px_per_col + px_per_col - 1);
+
+                if cell_x0 >= x0 && cell_x1 <= x1 && cell_y0 >= y0 && cell_y1 <= y1 {
+                    let (row, col) = (row as usize, col as usize);
+                    if self.framebuffer[row][col] != cell {
+                        self.framebuffer[row][col] = cell;
+                        self.generation += 1;
+                        self.mark_dirty(row, col);
+                    }
+                    continue;
+                }
+
+                for py in cell_y0.max(y0)..=cell_y1.min(y1) {
+                    for px in cell_x0.max(x0)..=cell_x1.min(x1) {
+                        self.write_native_pixel(px, py, on);
+                    }
+                }
+            }
+        }
+    }
+
+    /// Turn all pixels in the framebuffer on (black) or off (white)
+    ///
+    /// To show it on the display, call [`Self::flush()`].
+    pub fn clear_buffer(&mut self, on: bool) {
+        let byte = if on { 0xFF } else { 0x00 };
+        self.framebuffer = [[[byte; 3]; COLS]; ROWS];
+        self.mark_all_dirty();
+    }
+
+    /// Fill the framebuffer with a test pattern and flush it
+    ///
+    /// Writes the packed bytes directly instead of drawing pixel by pixel.
+    /// Useful when bringing up a new board, to check the wiring, the
+    /// address window and the bit mapping. See [`TestPattern`].
+    pub fn draw_test_pattern(
+        &mut self,
+        pattern: TestPattern,
+    ) -> Result<(), Error<SPI::Error, DC::Error>> {
+        for (row, cells) in self.framebuffer.iter_mut().enumerate() {
+            for (col, cell) in cells.iter_mut().enumerate() {
+                // The bits go column by column, two bits per pixel column
+                let byte = match pattern {
+                    TestPattern::Checkerboard if (row + col) % 2 == 0 => 0xFF,
+                    TestPattern::Checkerboard => 0x00,
+                    TestPattern::VerticalStripes => 0b11001100,
+                    TestPattern::HorizontalStripes => 0b10101010,
+                    TestPattern::SolidFill => 0xFF,
+                };
+                *cell = [byte; 3];
+            }
+        }
+        self.mark_all_dirty();
+        self.flush()
+    }
+
+    /// Whether the pixel at the given coords is black in the framebuffer
+    ///
+    /// Returns `None` if the coords are outside of the display.
+    pub fn get_pixel(&self, x: u16, y: u16) -> Option<bool> {
+        if x >= self.width || y >= self.height {
+            return None;
+        }
+        let (x, y) = self.native_point(x, y);
+        let (row, col, byte, bitmask) = self.native_location(x, y);
+        // In Gray4 mode dark and black gray count as black
+        let high_bit = bitmask & !(bitmask >> 1);
+        Some(self.framebuffer[row][col][byte] & high_bit != 0)
+    }
+
+    /// Render the framebuffer as ASCII art, for debugging
+    ///
+    /// Black pixels are written as `#` and white pixels as a space, with one
+    /// line per row of pixels. Displays wider than 80 pixels are scaled down
+    /// by the same factor in both directions, where a character is `#` if
+    /// any of the pixels it covers is black.
+    pub fn dump_ascii<W: core::fmt::Write>(&self, out: &mut W) -> core::fmt::Result {
+        let scale = self.width.div_ceil(ASCII_MAX_WIDTH).max(1);
+        for y in (0..self.height).step_by(scale as usize) {
+            for x in (0..self.width).step_by(scale as usize) {
+                let black = (y..(y + scale).min(self.height)).any(|py| {
+                    (x..(x + scale).min(self.width)).any(|px| self.get_pixel(px, py) == Some(true))
+                });
+                out.write_char(if black { '#' } else { ' ' })?;
+            }
+            out.write_char('\n')?;
+        }
+        Ok(())
+    }
+
+    /// Import a 1bpp image in horizontal byte format into the framebuffer
+    ///
+    /// This is the format that most image tools export for monochrome
+    /// displays: Rows of pixels from left to right, MSB first, with each row
+    /// padded to a full byte. A set bit is a black pixel.
+    /// The image is placed at the top left corner of the display.
+    ///
+    /// Returns an error if the image is bigger than the display or `data` is
+    /// too small for the given dimensions.
+    pub fn import_hmsb(
+        &mut self,
+        data: &[u8],
+        width: u16,
+        height: u16,
+    ) -> Result<(), Error<SPI::Error, DC::Error>> {
+        if width > self.width || height > self.height {
+            return Err(Error::OutOfBounds);
+        }
+        self.blit_hmsb(data, width, height, 0, 0)
+    }
+
+    /// Export the framebuffer as 1bpp image in horizontal byte format
+    ///
+    /// The opposite of [`Self::import_hmsb()`], covering the whole display.
+    /// Returns an error if `buf` is too small for the display dimensions.
+    pub fn export_hmsb(&self, buf: &mut [u8]) -> Result<(), Error<SPI::Error, DC::Error>> {
+        let stride = self.width.div_ceil(8) as usize;
+        let buf = buf
+            .get_mut(..stride * self.height as usize)
+            .ok_or(Error::BufferSize)?;
+        buf.fill(0);
+        for y in 0..self.height {
+            for x in 0..self.width {
+                if self.get_pixel(x, y) == Some(true) {
+                    buf[y as usize * stride + x as usize / 8] |= 0x80 >> (x % 8);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Copy an off-screen 1bpp framebuffer into the display's framebuffer
+    ///
+    /// Useful to compose an image off-screen, for example with an
+    /// embedded-graphics `Framebuffer<BinaryColor, ..>`, and then transfer it
+    /// at once. `fb` uses the same format as [`Self::import_hmsb()`], but it
+    /// can be placed anywhere and is clipped at the display edges.
+    ///
+    /// Returns an error if `fb` is too small for the given dimensions.
+    #[cfg(feature = "graphics")]
+    pub fn blit_binary_framebuf(
+        &mut self,
+        fb: &[u8],
+        width: u16,
+        height: u16,
+        origin: Point,
+    ) -> Result<(), Error<SPI::Error, DC::Error>> {
+        self.blit_hmsb(fb, width, height, origin.x, origin.y)
+    }
+
+    /// Copy a 1bpp bitmap into the framebuffer
+    ///
+    /// `data` is in the horizontal byte format of [`Self::import_hmsb()`],
+    /// `width` by `height` pixels, with its top left corner at `x`, `y`. The
+    /// bitmap is clipped at the display edges.
+    ///
+    /// If `x` is a multiple of 12 and `y` a multiple of 2, whole 12x2 pixel
+    /// cells of the framebuffer are packed at once, which is much faster than
+    /// drawing single pixels. Otherwise, or if the display is rotated,
+    /// mirrored or in [`ColorMode::Gray4`], the pixels are placed one by one.
+    ///
+    /// Returns an error if `data` is too small for the given dimensions.
+    /// To show it on the display, call [`Self::flush()`].
+    pub fn blit_packed(
+        &mut self,
+        x: u16,
+        y: u16,
+        width: u16,
+        height: u16,
+        data: &[u8],
+    ) -> Result<(), Error<SPI::Error, DC::Error>> {
+        self.blit_hmsb(data, width, height, x as i32, y as i32)
+    }
+
+    /// Copy a 1bpp image in horizontal byte format into the framebuffer
+    ///
+    /// The top left corner of the image is at `x0`, `y0`. Pixels outside of
+    /// the display are skipped.
+    fn blit_hmsb(
+        &mut self,
+        data: &[u8],
+        width: u16,
+        height: u16,
+        x0: i32,
+        y0: i32,
+    ) -> Result<(), Error<SPI::Error, DC::Error>> {
+        let stride = width.div_ceil(8) as usize;
+        if data.len() < stride * height as usize {
+            return Err(Error::BufferSize);
+        }
+        let aligned = self.orientation == Orientation::Portrait
+            && self.mirrored() == (false, false)
+            && self.color_mode == ColorMode::Mono
+            && x0 >= 0
+            && y0 >= 0
+            && x0 % PX_PER_COL as i32 == 0
+            && y0 % PX_PER_ROW as i32 == 0;
+        if !aligned {
+            for sy in 0..height {
+                let y = y0 + sy as i32;
+                if y < 0 || y >= self.height as i32 {
+                    continue;
+                }
+                for sx in 0..width {
+                    let x = x0 + sx as i32;
+                    if x < 0 || x >= self.width as i32 {
+                        continue;
+                    }
+                    let byte = data[sy as usize * stride + sx as usize / 8];
+                    self.write_pixel(x as u16, y as u16, byte & (0x80 >> (sx % 8)) != 0);
+                }
+            }
+            return Ok(());
+        }
+
+        let black = |sx: u16, sy: u16| {
+            data[sy as usize * stride + sx as usize / 8] & (0x80 >> (sx % 8)) != 0
+        };
+        let (width, height) = (width as i32, height as i32);
+        let (display_width, display_height) = (self.width as i32, self.height as i32);
+        for cy in (0..height).step_by(PX_PER_ROW as usize) {
+            for cx in (0..width).step_by(PX_PER_COL as usize) {
+                let (x, y) = (x0 + cx, y0 + cy);
+                let cell_width = PX_PER_COL as i32;
+                let cell_height = PX_PER_ROW as i32;
+
+                // Cells at the ragged edges of the image or display
+                if cx + cell_width > width
+                    || cy + cell_height > height
+                    || x + cell_width > display_width
+                    || y + cell_height > display_height
+                {
+                    for sy in cy..(cy + cell_height).min(height) {
+                        for sx in cx..(cx + cell_width).min(width) {
+                            let (x, y) = (x0 + sx, y0 + sy);
+                            if x < display_width && y < display_height {
+                                self.write_pixel(x as u16, y as u16, black(sx as u16, sy as u16));
+                            }
+                        }
+                    }
+                    continue;
+                }
+
+                // Same bit order as pixel_location
+                let mut cell = [0u8; 3];
+                for bit in 0..(cell_width * cell_height) {
+                    let (sx, sy) = (cx + bit / 2, cy + bit % 2);
+                    if black(sx as u16, sy as u16) {
+                        cell[bit as usize / 8] |= 0x80 >> (bit % 8);
+                    }
+                }
+                let (row, col) = ((y / cell_height) as usize, (x / cell_width) as usize);
+                if self.framebuffer[row][col] != cell {
+                    self.framebuffer[row][col] = cell;
+                    self.generation += 1;
+                    self.mark_dirty(row, col);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Turn a pixel in the framebuffer on (black) or off (white)
+    fn write_pixel(&mut self, x: u16, y: u16, black: bool) {
+        let (x, y) = self.native_point(x, y);
+        self.write_native_pixel(x, y, black);
+    }
+
+    /// Like [`Self::write_pixel()`], but in the panel's native orientation
+    fn write_native_pixel(&mut self, x: u16, y: u16, black: bool) {
+        let (row, col, byte, bitmask) = self.native_location(x, y);
+
+        let old = self.framebuffer[row][col][byte];
+        if black {
+            self.framebuffer[row][col][byte] |= bitmask
+        } else {
+            self.framebuffer[row][col][byte] &= !bitmask;
+        }
+        if self.framebuffer[row][col][byte] != old {
+            self.generation += 1;
+            self.mark_dirty(row, col);
+        }
+    }
+
+    /// Set a pixel in the framebuffer to a gray level, from 0 (white) to 3
+    /// (black)
+    ///
+    /// In mono mode the two darker levels are black.
+    #[cfg(feature = "graphics")]
+    fn write_gray(&mut self, x: u16, y: u16, darkness: u8) {
+        let (x, y) = self.native_point(x, y);
+        if self.color_mode == ColorMode::Mono {
+            self.write_native_pixel(x, y, darkness >= 2);
+            return;
+        }
+        let (row, col, byte, bitmask) = self.native_location(x, y);
+        let bits = darkness * (bitmask & !(bitmask << 1));
+
+        let old = self.framebuffer[row][col][byte];
+        self.framebuffer[row][col][byte] = (old & !bitmask) | bits;
+        if self.framebuffer[row][col][byte] != old {
+            self.generation += 1;
+            self.mark_dirty(row, col);
+        }
+    }
+
+    /// Where a pixel in the panel's native orientation is stored in the
+    /// framebuffer, for the current color mode
+    ///
+    /// If the controller mirrors the cells, the pixel is mirrored inside of
+    /// its cell, see [`Self::set_mirror_x()`].
+    fn native_location(&self, x: u16, y: u16) -> (usize, usize, usize, u8) {
+        let (mirror_x, mirror_y) = self.mirrored();
+        let px_per_col = self.px_per_col();
+        let x = if mirror_x {
+            x - x % px_per_col + (px_per_col - 1 - x % px_per_col)
+        } else {
+            x
+        };
+        let y = if mirror_y {
+            y - y % PX_PER_ROW + (PX_PER_ROW - 1 - y % PX_PER_ROW)
+        } else {
+            y
+        };
+        match self.color_mode {
+            ColorMode::Mono => pixel_location(x, y),
+            ColorMode::Gray4 => gray_pixel_location(x, y),
+        }
+    }
+
+    /// Pixels in the width of a framebuffer cell, for the current color mode
+    fn px_per_col(&self) -> u16 {
+        match self.color_mode {
+            ColorMode::Mono => PX_PER_COL,
+            ColorMode::Gray4 => GRAY_PX_PER_COL,
+        }
+    }
+
+    /// Current color mode, see [`ST7306Builder::color_mode()`]
+    pub fn color_mode(&self) -> ColorMode {
+        self.color_mode
+    }
+
+    /// Grow the dirty region to include a framebuffer cell
+    fn mark_dirty(&mut self, row: usize, col: usize) {
+        let (row, col) = (row as u16, col as u16);
+        self.dirty = Some(match self.dirty {
+            None => AddrWindow {
+                col_start: col,
+                col_end: col,
+                row_start: row,
+                row_end: row,
+            },
+            Some(dirty) => AddrWindow {
+                col_start: dirty.col_start.min(col),
+                col_end: dirty.col_end.max(col),
+                row_start: dirty.row_start.min(row),
+                row_end: dirty.row_end.max(row),
+            },
+        });
+    }
+
+    /// Width and height of the panel in its native orientation
+    fn native_size(&self) -> (u16, u16) {
+        match self.orientation {
+            Orientation::Portrait | Orientation::PortraitSwapped => (self.width, self.height),
+            Orientation::Landscape | Orientation::LandscapeSwapped => (self.height, self.width),
+        }
+    }
+
+    /// Rotate pixel coordinates into the panel's native orientation
+    fn native_point(&self, x: u16, y: u16) -> (u16, u16) {
+        let (width, height) = self.native_size();
+        match self.orientation {
+            Orientation::Portrait => (x, y),
+            Orientation::Landscape => (width - 1 - y, x),
+            Orientation::PortraitSwapped => (width - 1 - x, height - 1 - y),
+            Orientation::LandscapeSwapped => (y, height - 1 - x),
+        }
+    }
+
+    /// Rotate native pixel coordinates into the current orientation
+    ///
+    /// The inverse of [`Self::native_point()`].
+    #[cfg(feature = "dither")]
+    fn logical_point(&self, x: u16, y: u16) -> (u16, u16) {
+        let (width, height) = self.native_size();
+        match self.orientation {
+            Orientation::Portrait => (x, y),
+            Orientation::Landscape => (y, width - 1 - x),
+            Orientation::PortraitSwapped => (width - 1 - x, height - 1 - y),
+            Orientation::LandscapeSwapped => (height - 1 - y, x),
+        }
+    }
+
+    /// Rotate a non-empty area, clamped to the display, into the panel's
+    /// native orientation
+    #[cfg(feature = "graphics")]
+    fn native_area(&self, area: Rectangle) -> Rectangle {
+        let bottom_right = area.bottom_right().unwrap_or(area.top_left);
+        let (x0, y0) = self.native_point(area.top_left.x as u16, area.top_left.y as u16);
+        let (x1, y1) = self.native_point(bottom_right.x as u16, bottom_right.y as u16);
+        Rectangle::with_corners(
+            Point::new(x0.min(x1) as i32, y0.min(y1) as i32),
+            Point::new(x0.max(x1) as i32, y0.max(y1) as i32),
+        )
+    }
+}
+
+/// Error of the functions that read from the controller
+type ReadError<SPI, DC> = Error<<SPI as spi::Write<u8>>::Error, <DC as OutputPin>::Error>;
+
+/// Reading registers needs an SPI that can also receive
+impl<SPI, DC, CS, RST, const COLS: usize, const ROWS: usize> ST7306<SPI, DC, CS, RST, COLS, ROWS>
+where
+    SPI: spi::Write<u8> + spi::Transfer<u8, Error = <SPI as spi::Write<u8>>::Error>,
+    DC: OutputPin,
+    CS: OutputPin<Error = DC::Error>,
+    RST: OutputPin<Error = DC::Error>,
+{
+    /// Creates a new driver instance and checks that a panel responds
+    ///
+    /// Like [`Self::new_and_probe()`], but for an SPI that can also read.
+    /// After the hard reset it
+    /// reads the display ID with [`Self::read_display_id()`]. Without a
+    /// panel, or with MISO not connected, the ID reads as all zeros or all
+    /// ones and [`Error::NoResponse`] is returned, so the application fails
+    /// at startup instead of drawing into the void. Returns
+    /// [`Error::Config`] if the configuration is invalid.
+    ///
+    /// With [`ST7306Builder::external_reset()`] the hard reset is skipped.
+    pub fn new_and_probe_id<DELAY>(
+        spi: SPI,
+        dc: DC,
+        cs: CS,
+        rst: RST,
+        delay: &mut DELAY,
+        config: ST7306Builder,
+    ) -> Result<Self, ReadError<SPI, DC>>
+    where
+        DELAY: DelayMs<u8>,
+    {
+        let mut display = Self::build_and_reset(spi, dc, cs, rst, delay, config)?;
+        let id = display.read_display_id()?;
+        if id == [0x00; 3] || id == [0xFF; 3] {
+            return Err(Error::NoResponse);
+        }
+        Ok(display)
+    }
+
+    /// Read the 3 byte display ID with RDDID
+    ///
+    /// The bytes are the manufacturer ID, the module version ID and the
+    /// module ID, the same as RDID1, RDID2 and RDID3 return.
+    pub fn read_display_id(&mut self) -> Result<[u8; 3], ReadError<SPI, DC>> {
+        let mut id = [0; 3];
+        self.read_command(Instruction::RDDID, &mut id)?;
+        Ok(id)
+    }
+
+    /// Read the ID registers with RDID1, RDID2 and RDID3
+    ///
+    /// Same bytes as [`Self::read_display_id()`], but with a single byte
+    /// read each, which has no dummy bit. See [`Self::set_id()`].
+    pub fn read_id(&mut self) -> Result<[u8; 3], ReadError<SPI, DC>> {
+        let mut id = [0; 3];
+        for (command, byte) in [Instruction::RDID1, Instruction::RDID2, Instruction::RDID3]
+            .into_iter()
+            .zip(id.iter_mut())
+        {
+            let mut reply = [0];
+            self.read_raw(command, &mut reply)?;
+            *byte = reply[0];
+        }
+        Ok(id)
+    }
+
+    /// Read the 32 bit display status with RDDST
+    pub fn read_status(&mut self) -> Result<u32, ReadError<SPI, DC>> {
+        let mut status = [0; 4];
+        self.read_command(Instruction::RDDST, &mut status)?;
+        Ok(u32::from_be_bytes(status))
+    }
+
+    /// Read `N` bytes from the NVM with NVMRD
+    ///
+    /// The NVM holds the factory trim of the voltages and the oscillator.
+    /// Reading is enabled with NVMRDEN before NVMRD and disabled again
+    /// afterwards. At most 16 bytes can be read at once, a larger `N`
+    /// returns [`Error::BufferSize`].
+    ///
+    /// The meaning of the bytes depends on the panel, check the datasheet
+    /// of your module.
+    pub fn nvm_read<const N: usize>(&mut self) -> Result<[u8; N], ReadError<SPI, DC>> {
+        if N > READ_MAX {
+            return Err(Error::BufferSize);
+        }
+        let mut data = [0; N];
+        self.write_command(Instruction::NVMRDEN, &[NVM_ENABLE])?;
+        let read = self.read_command(Instruction::NVMRD, &mut data);
+        // Disable reading again, even if the read failed
+        self.write_command(Instruction::NVMRDEN, &[NVM_DISABLE])?;
+        read?;
+        Ok(data)
+    }
+
+    /// Build the voltage config from the factory trim in the NVM
+    ///
+    /// Reads the first six bytes with [`Self::nvm_read()`], laid out as:
+    ///
+    /// | Byte | Register          |
+    /// |------|-------------------|
+    /// | 0    | GCTRL VGH         |
+    /// | 1    | GCTRL VGL         |
+    /// | 2    | VSHPCTRL          |
+    /// | 3    | VSLPCTRL          |
+    /// | 4    | VSHNCTRL          |
+    /// | 5    | VSLNCTRL          |
+    ///
+    /// The NVM has one trim per source voltage, it's used for all four gray
+    /// levels. The config replaces the driver's voltages and is written by
+    /// the next [`Self::load_config()`]. Codes outside of the ranges of
+    /// [`VoltageConfig`] return [`ConfigError::VoltageOutOfRange`] and
+    /// leave the voltages unchanged.
+    pub fn load_config_from_nvm(&mut self) -> Result<VoltageConfig, ReadError<SPI, DC>> {
+        let [vgh, vgl, vshp, vslp, vshn, vsln] = self.nvm_read::<6>()?;
+        let voltage = VoltageConfig {
+            gate: [vgh, vgl],
+            vshp: [vshp; 4],
+            vslp: [vslp; 4],
+            vshn: [vshn; 4],
+            vsln: [vsln; 4],
+        };
+        if !voltage.in_range() {
+            return Err(ConfigError::VoltageOutOfRange.into());
+        }
+        self.voltage = voltage;
+        Ok(voltage)
+    }
+
+    /// Send a read command and receive up to 16 bytes of reply
+    ///
+    /// For multi-byte reads like RDDID and RDDST the controller clocks out
+    /// one dummy bit before the data, like other Sitronix controllers. So the
+    /// reply is shifted by one bit and one more byte than the reply has is
+    /// clocked in. The panel's data output must be wired to the MCU's MISO.
+    fn read_command(
+        &mut self,
+        command: Instruction,
+        reply: &mut [u8],
+    ) -> Result<(), ReadError<SPI, DC>> {
+        let mut buf = [0; READ_MAX + 1];
+        let received = buf.get_mut(..reply.len() + 1).ok_or(Error::BufferSize)?;
+        self.read_raw(command, received)?;
+
+        // Drop the dummy bit
+        for (i, byte) in reply.iter_mut().enumerate() {
+            *byte = (received[i] << 1) | (received[i + 1] >> 7);
+        }
+        Ok(())
+    }
+
+    /// Send a read command and clock in exactly `buf.len()` bytes
+    fn read_raw(&mut self, command: Instruction, buf: &mut [u8]) -> Result<(), ReadError<SPI, DC>> {
+        self.cs.set_low().map_err(Error::Pin)?;
+        self.dc.set_low().map_err(Error::Pin)?;
+        spi::Write::write(&mut self.spi, &[command as u8]).map_err(Error::Spi)?;
+        self.dc.set_high().map_err(Error::Pin)?;
+        self.spi.transfer(buf).map_err(Error::Spi)?;
+        self.cs.set_high().map_err(Error::Pin)?;
+        Ok(())
+    }
+}
+
+#[cfg(feature = "graphics")]
+extern crate embedded_graphics;
+#[cfg(feature = "graphics")]
+use self::embedded_graphics::{
+    draw_target::DrawTarget,
+    image::{Image, ImageRaw},
+    pixelcolor::{BinaryColor, Gray2, Gray8, Rgb565},
+    prelude::*,
+    primitives::Rectangle,
+};
+
+/// Default color to brightness mapping
+///
+/// Scales the channels to 8 bits and weighs them by perceived luminance
+/// (ITU-R BT.601). Returns 0 (black) to 255 (white).
+#[cfg(feature = "graphics")]
+fn col_to_bright(color: Rgb565) -> u8 {
+    let r = color.r() as u32 * 255 / 31;
+    let g = color.g() as u32 * 255 / 63;
+    let b = color.b() as u32 * 255 / 31;
+    ((r * 77 + g * 150 + b * 29) >> 8) as u8
+}
+
+#[cfg(feature = "graphics")]
+// TODO: Remove color support from here
+impl<SPI, DC, CS, RST, const COLS: usize, const ROWS: usize> DrawTarget
+    for ST7306<SPI, DC, CS, RST, COLS, ROWS>
+where
+    SPI: spi::Write<u8>,
+    DC: OutputPin,
+    CS: OutputPin<Error = DC::Error>,
+    RST: OutputPin<Error = DC::Error>,
+{
+    type Error = Error<SPI::Error, DC::Error>;
+    type Color = Rgb565;
+
+    fn draw_iter<I>(&mut self, pixels: I) -> Result<(), Self::Error>
+    where
+        I: IntoIterator<Item = Pixel<Self::Color>>,
+    {
+        // ATTENTION!! After calling the draw functions, you have to flush.
+        // It doesn't auto flush because you might want to combine several draw
+        // operations together and flush them all at the same time. This avoids
+        // artifacts while the screen is refreshing.
+        // TODO: I think embedded-graphics has affordances for that.
+        self.draw_pixels(pixels, false)
+    }
+
+    fn fill_contiguous<I>(&mut self, area: &Rectangle, colors: I) -> Result<(), Self::Error>
+    where
+        I: IntoIterator<Item = Self::Color>,
+    {
+        // Clamp area to drawable part of the display target
+        let drawable_area = area.intersection(&self.bounding_box());
+        let pixels = area
+            .points()
+            .zip(colors)
+            .filter(|(pos, _color)| drawable_area.contains(*pos))
+            .map(|(pos, color)| Pixel(pos, color));
+        self.draw_pixels(pixels, false)
+    }
+
+    fn fill_solid(&mut self, area: &Rectangle, color: Self::Color) -> Result<(), Self::Error> {
+        let brightness = self.luminance(color);
+        // Same thresholds as drawing single pixels
+        let on = match self.color_mode {
+            // Dithered grays don't fill whole bytes either
+            ColorMode::Mono
+                if self.dither_mode != DitherMode::None && !matches!(brightness, 0 | 255) =>
+            {
+                return self.draw_iter(area.points().map(|pos| Pixel(pos, color)));
+            }
+            ColorMode::Mono => brightness < BLACK_THRESHOLD,
+            ColorMode::Gray4 => match 3 - (brightness >> 6) {
+                0 => false,
+                3 => true,
+                // Intermediate grays don't fill whole bytes
+                _ => return self.draw_iter(area.points().map(|pos| Pixel(pos, color))),
+            },
+        };
+
+        let area = area.intersection(&self.bounding_box());
+        self.fill_rect(
+            area.top_left.x as u16,
+            area.top_left.y as u16,
+            area.size.width as u16,
+            area.size.height as u16,
+            on,
+        );
+        Ok(())
+    }
+
+    fn clear(&mut self, color: Self::Color) -> Result<(), Self::Error> {
+        let black = self.luminance(color) < BLACK_THRESHOLD;
+        self.clear_buffer(black);
+        self.clear_ram_to(black)?;
+        // Framebuffer and RAM match again
+        self.dirty = None;
+        Ok(())
+    }
+}
+
+#[cfg(feature = "graphics")]
+impl<SPI, DC, CS, RST, const COLS: usize, const ROWS: usize> OriginDimensions
+    for ST7306<SPI, DC, CS, RST, COLS, ROWS>
+where
+    SPI: spi::Write<u8>,
+    DC: OutputPin,
+    CS: OutputPin,
+    RST: OutputPin,
+{
+    fn size(&self) -> Size {
+        Size::new(self.width as u32, self.height as u32)
+    }
+}
+
+/// Direction of a gradient, see [`ST7306::fill_gradient()`]
+#[cfg(feature = "dither")]
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Axis {
+    /// From left to right
+    Horizontal,
+    /// From top to bottom
+    Vertical,
+}
+
+/// How colors are turned into black and white, see
+/// [`ST7306::set_dither_mode()`]
+#[cfg(feature = "graphics")]
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum DitherMode {
+    /// Everything darker than half brightness is black, the rest white
+    #[default]
+    None,
+    /// Ordered dithering with a 4x4 Bayer matrix
+    ///
+    /// Grays become patterns of black and white pixels with 17 distinct
+    /// levels. Looks much better for images than a hard threshold and, unlike
+    /// error diffusion, needs no buffer.
+    Bayer4x4,
+}
+
+/// Threshold matrix of [`DitherMode::Bayer4x4`], in sixteenths
+#[cfg(feature = "graphics")]
+const BAYER_4X4: [[u8; 4]; 4] = [[0, 8, 2, 10], [12, 4, 14, 6], [3, 11, 1, 9], [15, 7, 13, 5]];
+
+/// Whether a pixel of this brightness is black with 4x4 ordered dithering
+#[cfg(feature = "graphics")]
+fn bayer_black(x: u16, y: u16, brightness: u8) -> bool {
+    let threshold = BAYER_4X4[y as usize % 4][x as usize % 4] * 16 + 8;
+    brightness < threshold
+}
+
+/// Rotation in steps of 90 degrees, clockwise
+#[cfg(feature = "graphics")]
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Rotation90 {
+    Deg0,
+    Deg90,
+    Deg180,
+    Deg270,
+}
+
+#[cfg(feature = "graphics")]
+impl Rotation90 {
+    /// Rotate `point` around `origin`
+    fn apply(self, origin: Point, point: Point) -> Point {
+        let d = point - origin;
+        let d = match self {
+            Rotation90::Deg0 => d,
+            Rotation90::Deg90 => Point::new(-d.y, d.x),
+            Rotation90::Deg180 => Point::new(-d.x, -d.y),
+            Rotation90::Deg270 => Point::new(d.y, -d.x),
+        };
+        origin + d
+    }
+
+    /// The rotation that undoes this one
+    fn inverse(self) -> Self {
+        match self {
+            Rotation90::Deg0 => Rotation90::Deg0,
+            Rotation90::Deg90 => Rotation90::Deg270,
+            Rotation90::Deg180 => Rotation90::Deg180,
+            Rotation90::Deg270 => Rotation90::Deg90,
+        }
+    }
+}
+
+/// Draw target that rotates everything drawn to it, see [`ST7306::draw_rotated()`]
+#[cfg(feature = "graphics")]
+struct Rotated<'a, T> {
+    target: &'a mut T,
+    origin: Point,
+    rotation: Rotation90,
+}
+
+#[cfg(feature = "graphics")]
+impl<T: DrawTarget> DrawTarget for Rotated<'_, T> {
+    type Color = T::Color;
+    type Error = T::Error;
+
+    fn draw_iter<I>(&mut self, pixels: I) -> Result<(), Self::Error>
+    where
+        I: IntoIterator<Item = Pixel<Self::Color>>,
+    {
+        let (origin, rotation) = (self.origin, self.rotation);
+        self.target.draw_iter(
+            pixels
+                .into_iter()
+                .map(|Pixel(point, color)| Pixel(rotation.apply(origin, point), color)),
+        )
+    }
+}
+
+#[cfg(feature = "graphics")]
+impl<T: DrawTarget> Dimensions for Rotated<'_, T> {
+    /// The display area, as seen from the rotated coordinates
+    fn bounding_box(&self) -> Rectangle {
+        let area = self.target.bounding_box();
+        match area.bottom_right() {
+            Some(bottom_right) => {
+                let inverse = self.rotation.inverse();
+                Rectangle::with_corners(
+                    inverse.apply(self.origin, area.top_left),
+                    inverse.apply(self.origin, bottom_right),
+                )
+            }
+            None => area,
+        }
+    }
+}
+
+/// Draws with [`BinaryColor`], see [`ST7306::binary()`]
+#[cfg(feature = "graphics")]
+pub struct BinaryTarget<'a, SPI, DC, CS, RST, const COLS: usize, const ROWS: usize>
+where
+    SPI: spi::Write<u8>,
+    DC: OutputPin,
+    CS: OutputPin,
+    RST: OutputPin,
+{
+    display: &'a mut ST7306<SPI, DC, CS, RST, COLS, ROWS>,
+}
+
+#[cfg(feature = "graphics")]
+impl<SPI, DC, CS, RST, const COLS: usize, const ROWS: usize> DrawTarget
+    for BinaryTarget<'_, SPI, DC, CS, RST, COLS, ROWS>
+where
+    SPI: spi::Write<u8>,
+    DC: OutputPin,
+    CS: OutputPin<Error = DC::Error>,
+    RST: OutputPin<Error = DC::Error>,
+{
+    type Error = Error<SPI::Error, DC::Error>;
+    type Color = BinaryColor;
+
+    fn draw_iter<I>(&mut self, pixels: I) -> Result<(), Self::Error>
+    where
+        I: IntoIterator<Item = Pixel<Self::Color>>,
+    {
+        let bounding_box = self.display.bounding_box();
+        for Pixel(coord, color) in pixels.into_iter() {
+            if bounding_box.contains(coord) {
+                self.display
+                    .write_pixel(coord.x as u16, coord.y as u16, color.is_on());
+            }
+        }
+        Ok(())
+    }
+
+    fn fill_solid(&mut self, area: &Rectangle, color: Self::Color) -> Result<(), Self::Error> {
+        let area = area.intersection(&self.display.bounding_box());
+        self.display.fill_rect(
+            area.top_left.x as u16,
+            area.top_left.y as u16,
+            area.size.width as u16,
+            area.size.height as u16,
+            color.is_on(),
+        );
+        Ok(())
+    }
+
+    /// Only clears the framebuffer, call [`ST7306::flush()`] to show it
+    fn clear(&mut self, color: Self::Color) -> Result<(), Self::Error> {
+        self.display.clear_buffer(color.is_on());
+        Ok(())
+    }
+}
+
+#[cfg(feature = "graphics")]
+impl<SPI, DC, CS, RST, const COLS: usize, const ROWS: usize> OriginDimensions
+    for BinaryTarget<'_, SPI, DC, CS, RST, COLS, ROWS>
+where
+    SPI: spi::Write<u8>,
+    DC: OutputPin,
+    CS: OutputPin,
+    RST: OutputPin,
+{
+    fn size(&self) -> Size {
+        self.display.size()
+    }
+}
+
+/// Draws with 4 gray levels, see [`ST7306::gray4()`]
+#[cfg(feature = "graphics")]
+pub struct Gray4Target<'a, SPI, DC, CS, RST, const COLS: usize, const ROWS: usize>
+where
+    SPI: spi::Write<u8>,
+    DC: OutputPin,
+    CS: OutputPin,
+    RST: OutputPin,
+{
+    display: &'a mut ST7306<SPI, DC, CS, RST, COLS, ROWS>,
+}
+
+#[cfg(feature = "graphics")]
+impl<SPI, DC, CS, RST, const COLS: usize, const ROWS: usize> DrawTarget
+    for Gray4Target<'_, SPI, DC, CS, RST, COLS, ROWS>
+where
+    SPI: spi::Write<u8>,
+    DC: OutputPin,
+    CS: OutputPin<Error = DC::Error>,
+    RST: OutputPin<Error = DC::Error>,
+{
+    type Error = Error<SPI::Error, DC::Error>;
+    type Color = Gray2;
+
+    fn draw_iter<I>(&mut self, pixels: I) -> Result<(), Self::Error>
+    where
+        I: IntoIterator<Item = Pixel<Self::Color>>,
+    {
+        let bounding_box = self.display.bounding_box();
+        for Pixel(coord, color) in pixels.into_iter() {
+            if bounding_box.contains(coord) {
+                self.display
+                    .write_gray(coord.x as u16, coord.y as u16, 3 - color.luma());
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(feature = "graphics")]
+impl<SPI, DC, CS, RST, const COLS: usize, const ROWS: usize> OriginDimensions
+    for Gray4Target<'_, SPI, DC, CS, RST, COLS, ROWS>
+where
+    SPI: spi::Write<u8>,
+    DC: OutputPin,
+    CS: OutputPin,
+    RST: OutputPin,
+{
+    fn size(&self) -> Size {
+        self.display.size()
+    }
+}
+
+/// Draws with 256 dithered gray levels, see [`ST7306::gray8()`]
+#[cfg(feature = "graphics")]
+pub struct Gray8Target<'a, SPI, DC, CS, RST, const COLS: usize, const ROWS: usize>
+where
+    SPI: spi::Write<u8>,
+    DC: OutputPin,
+    CS: OutputPin,
+    RST: OutputPin,
+{
+    display: &'a mut ST7306<SPI, DC, CS, RST, COLS, ROWS>,
+}
+
+#[cfg(feature = "graphics")]
+impl<SPI, DC, CS, RST, const COLS: usize, const ROWS: usize> DrawTarget
+    for Gray8Target<'_, SPI, DC, CS, RST, COLS, ROWS>
+where
+    SPI: spi::Write<u8>,
+    DC: OutputPin,
+    CS: OutputPin<Error = DC::Error>,
+    RST: OutputPin<Error = DC::Error>,
+{
+    type Error = Error<SPI::Error, DC::Error>;
+    type Color = Gray8;
+
+    fn draw_iter<I>(&mut self, pixels: I) -> Result<(), Self::Error>
+    where
+        I: IntoIterator<Item = Pixel<Self::Color>>,
+    {
+        let bounding_box = self.display.bounding_box();
+        for Pixel(coord, color) in pixels.into_iter() {
+            if !bounding_box.contains(coord) {
+                continue;
+            }
+            let (x, y) = (coord.x as u16, coord.y as u16);
+            match self.display.color_mode {
+                ColorMode::Mono => {
+                    let black = bayer_black(x, y, color.luma());
+                    self.display.write_pixel(x, y, black);
+                }
+                ColorMode::Gray4 => self.display.write_gray(x, y, 3 - (color.luma() >> 6)),
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(feature = "graphics")]
+impl<SPI, DC, CS, RST, const COLS: usize, const ROWS: usize> OriginDimensions
+    for Gray8Target<'_, SPI, DC, CS, RST, COLS, ROWS>
+where
+    SPI: spi::Write<u8>,
+    DC: OutputPin,
+    CS: OutputPin,
+    RST: OutputPin,
+{
+    fn size(&self) -> Size {
+        self.display.size()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate std;
+
+    use super::*;
+    use embedded_hal_mock::eh0::digital::{Mock as PinMock, State, Transaction as PinTransaction};
+    use embedded_hal_mock::eh0::spi::{Mock as SpiMock, Transaction as SpiTransaction};
+    #[cfg(feature = "async")]
+    use embedded_hal_mock::eh1::{
+        delay::NoopDelay as Eh1NoopDelay,
+        digital::{Mock as Eh1PinMock, State as Eh1State, Transaction as Eh1PinTransaction},
+        spi::{Mock as Eh1SpiMock, Transaction as Eh1SpiTransaction},
+    };
+    use std::vec;
+    use std::vec::Vec;
+
+    type MockDisplay = ST7306<SpiMock, PinMock, PinMock, PinMock, 2, 2>;
+
+    /// 24x4 pixels, two cells wide and two high
+    fn display(
+        spi: &[SpiTransaction],
+        dc: &[PinTransaction],
+        cs: &[PinTransaction],
+    ) -> MockDisplay {
+        let mut display: MockDisplay = ST7306Builder::new()
+            .dimensions(24, 4)
+            .build(
+                SpiMock::new(spi),
+                PinMock::new(dc),
+                PinMock::new(cs),
+                PinMock::new(&[]),
+            )
+            .unwrap();
+        // Like after init
+        display.ram_window = Some(display.addr_window);
+        display
+    }
+
+    /// Expect a pin to be set to these states, in order
+    fn pin_states(states: &[State]) -> Vec<PinTransaction> {
+        states.iter().copied().map(PinTransaction::set).collect()
+    }
+
+    /// Expected bus traffic, built command by command
+    #[derive(Default)]
+    struct Expect {
+        spi: Vec<SpiTransaction>,
+        dc: Vec<PinTransaction>,
+        cs: Vec<PinTransaction>,
+    }
+
+    impl Expect {
+        /// A command as sent by [`ST7306::write_command()`]
+        fn command(mut self, command: Instruction, params: &[u8]) -> Self {
+            self.cs.push(PinTransaction::set(State::Low));
+            self.dc.push(PinTransaction::set(State::Low));
+            self.spi.push(SpiTransaction::write(vec![command as u8]));
+            if !params.is_empty() {
+                self = self.data(params);
+            }
+            self.cs.push(PinTransaction::set(State::High));
+            self
+        }
+
+        /// Data after [`ST7306::start_data()`], in one SPI write
+        fn data(mut self, data: &[u8]) -> Self {
+            self.cs.push(PinTransaction::set(State::Low));
+            self.dc.push(PinTransaction::set(State::High));
+            self.spi.push(SpiTransaction::write(data.to_vec()));
+            self
+        }
+
+        /// More data without another [`ST7306::start_data()`]
+        fn more_data(mut self, data: &[u8]) -> Self {
+            self.spi.push(SpiTransaction::write(data.to_vec()));
+            self
+        }
+
+        /// A read command as sent by `read_raw()`
+        fn read(mut self, command: Instruction, reply: &[u8]) -> Self {
+            self.cs.push(PinTransaction::set(State::Low));
+            self.dc.push(PinTransaction::set(State::Low));
+            self.spi.push(SpiTransaction::write(vec![command as u8]));
+            self.dc.push(PinTransaction::set(State::High));
+            let clocked_out = vec![0; reply.len()];
+            self.spi
+                .push(SpiTransaction::transfer(clocked_out, reply.to_vec()));
+            self.cs.push(PinTransaction::set(State::High));
+            self
+        }
+
+        fn display(self) -> MockDisplay {
+            display(&self.spi, &self.dc, &self.cs)
+        }
+    }
+
+    /// Check that all expected transactions happened
+    fn done<const COLS: usize, const ROWS: usize>(
+        mut display: ST7306<SpiMock, PinMock, PinMock, PinMock, COLS, ROWS>,
+    ) {
+        display.spi.done();
+        display.dc.done();
+        display.cs.done();
+        display.rst.done();
+    }
+
+    #[test]
+    fn set_pixel_bit_mapping() {
+        // Every position within a cell, in the second row and column of cells
+        for dy in 0..PX_PER_ROW {
+            for dx in 0..PX_PER_COL {
+                let mut display = display(&[], &[], &[]);
+                display
+                    .set_pixel(PX_PER_COL + dx, PX_PER_ROW + dy, 0)
+                    .unwrap();
+
+                // Pixels are stored column by column, two bits per column,
+                // starting at the MSB of the first byte.
+                let bit = (dx * 2 + dy) as usize;
+                let mut cell = [0; 3];
+                cell[bit / 8] = 0x80 >> (bit % 8);
+
+                let framebuffer = display.framebuffer();
+                assert_eq!(framebuffer[1][1], cell, "pixel {dx},{dy}");
+                assert_eq!(framebuffer[0], [[0; 3]; 2]);
+                assert_eq!(framebuffer[1][0], [0; 3]);
+                done(display);
+            }
+        }
+    }
+
+    #[test]
+    fn build_in_place() {
+        let mut slot = MaybeUninit::uninit();
+        let display: &mut MockDisplay = ST7306Builder::new()
+            .dimensions(24, 4)
+            .tearing(true)
+            .build_in_place(
+                &mut slot,
+                SpiMock::new(&[]),
+                PinMock::new(&[]),
+                PinMock::new(&[]),
+                PinMock::new(&[]),
+            )
+            .unwrap();
+        assert_eq!(display.framebuffer(), &[[[0; 3]; 2]; 2]);
+        assert_eq!(display.dimensions(), (24, 4));
+        assert!(display.te_enable);
+        assert!(display.needs_flush());
+        display.set_pixel(0, 0, 0).unwrap();
+        // SAFETY: Initialized by build_in_place
+        done(unsafe { slot.assume_init() });
+
+        // Too large for the framebuffer
+        let mut slot = MaybeUninit::<MockDisplay>::uninit();
+        let mut spi = SpiMock::new(&[]);
+        let mut pin = PinMock::new(&[]);
+        let result = ST7306Builder::new().dimensions(24, 6).build_in_place(
+            &mut slot,
+            spi.clone(),
+            pin.clone(),
+            pin.clone(),
+            pin.clone(),
+        );
+        assert!(matches!(result, Err(ConfigError::FramebufferTooSmall)));
+        spi.done();
+        pin.done();
+    }
+
+    #[test]
+    fn spi_too_fast() {
+        let mut spi = SpiMock::new(&[]);
+        let mut pin = PinMock::new(&[]);
+        let build = |hz| {
+            ST7306Builder::new()
+                .dimensions(24, 4)
+                .spi_frequency(hz)
+                .build::<_, _, _, _, 2, 2>(spi.clone(), pin.clone(), pin.clone(), pin.clone())
+        };
+        assert!(matches!(
+            build(MAX_SPI_HZ + 1),
+            Err(ConfigError::SpiTooFast)
+        ));
+
+        let display = build(MAX_SPI_HZ).unwrap();
+        assert_eq!(display.with_spi_frequency(MAX_SPI_HZ), Ok(()));
+        assert_eq!(
+            display.with_spi_frequency(MAX_SPI_HZ + 1),
+            Err(ConfigError::SpiTooFast)
+        );
+        // Still usable after the error
+        assert_eq!(display.validate_config(), Ok(()));
+        spi.done();
+        pin.done();
+    }
+
+    #[test]
+    fn panel_geometry() {
+        let geometry = PanelGeometry::ST7306;
+        assert_eq!(
+            geometry.validate(300, 400, 0, 0),
+            Ok(AddrWindow {
+                col_start: 0,
+                col_end: 24,
+                row_start: 0,
+                row_end: 199,
+            })
+        );
+        assert_eq!(
+            geometry
+                .validate(720, 400, 0, 0)
+                .map(|w| (w.col_end, w.row_end)),
+            Ok((COL_MAX, ROW_MAX))
+        );
+        assert_eq!(
+            geometry.validate(0, 400, 0, 0),
+            Err(GeometryError::EmptyDisplay)
+        );
+        assert_eq!(
+            geometry.validate(300, 401, 0, 0),
+            Err(GeometryError::UnalignedSize)
+        );
+        assert_eq!(
+            geometry.validate(300, 400, 36, 0),
+            Err(GeometryError::WindowOutOfRange)
+        );
+        assert_eq!(
+            geometry.validate(300, 2, 0, u16::MAX),
+            Err(GeometryError::WindowOutOfRange)
+        );
+        // The builder reports the same problem
+        assert_eq!(
+            ConfigError::from(GeometryError::UnalignedSize),
+            ConfigError::UnalignedSize
+        );
+    }
+
+    #[test]
+    fn ram_window() {
+        let config = ST7306Builder::new().dimensions(24, 4).offset(1, 2);
+        assert_eq!(config.ram_columns(), 1..=2);
+        assert_eq!(config.ram_rows(), 2..=3);
+
+        // Partial cells are rejected
+        let config = ST7306Builder::new().dimensions(25, 5);
+        assert_eq!(
+            check_config::<3, 3>(
+                25,
+                5,
+                &config.addr_window(),
+                &config.tuning,
+                &config.voltage,
+                2
+            ),
+            Err(ConfigError::UnalignedSize)
+        );
+
+        let config = ST7306Builder::new();
+        assert_eq!(config.ram_columns(), 0..=COL_MAX);
+        assert_eq!(config.ram_rows(), 0..=ROW_MAX);
+    }
+
+    #[test]
+    fn gate_lines_follow_height() {
+        assert_eq!(ST7306Builder::new().gate_line_count(), 100);
+        let config = ST7306Builder::new().dimensions(24, 240);
+        assert_eq!(config.gate_line_count(), 60);
+        assert_eq!(config.gate_lines(50).gate_line_count(), 50);
+        let tuning = TuningConfig::default();
+        let voltage = VoltageConfig::default();
+        let window = config.addr_window();
+        assert_eq!(
+            check_config::<0, 0>(24, 240, &window, &tuning, &voltage, 101),
+            Err(ConfigError::InvalidGateLines)
+        );
+        assert_eq!(
+            check_config::<0, 0>(24, 240, &window, &tuning, &voltage, 0),
+            Err(ConfigError::InvalidGateLines)
+        );
+
+        let voltage = VoltageConfig::default();
+        let tuning = TuningConfig::default();
+        let expect = Expect::default()
+            .command(Instruction::NVMLOADCTRL, &[0b10001, 0])
+            .command(Instruction::BSTEN, &[0x01])
+            .command(Instruction::GCTRL, &voltage.gate)
+            .command(Instruction::VSHPCTRL, &voltage.vshp)
+            .command(Instruction::VSLPCTRL, &voltage.vslp)
+            .command(Instruction::VSHNCTRL, &voltage.vshn)
+            .command(Instruction::VSLNCTRL, &voltage.vsln)
+            .command(Instruction::OSCSET, &tuning.oscillator)
+            .command(Instruction::FRCTRL, &[0x12])
+            .command(Instruction::GTUPEQH, &tuning.hpm_eq)
+            .command(Instruction::GTUPEQL, &tuning.lpm_eq)
+            .command(Instruction::SOUEQ, &[tuning.source_eq])
+            .command(Instruction::GATESET, &[60]);
+        let mut display: ST7306Streaming<SpiMock, PinMock, PinMock, PinMock> = config
+            .build(
+                SpiMock::new(&expect.spi),
+                PinMock::new(&expect.dc),
+                PinMock::new(&expect.cs),
+                PinMock::new(&[]),
+            )
+            .unwrap();
+        display.load_config().unwrap();
+        done(display);
+    }
+
+    #[test]
+    fn external_reset() {
+        let mut display: ST7306Streaming<SpiMock, PinMock, PinMock, NoPin<_>> =
+            ST7306Builder::new()
+                .external_reset()
+                .build(
+                    SpiMock::new(&[]),
+                    PinMock::new(&[]),
+                    PinMock::new(&[]),
+                    NoPin::new(),
+                )
+                .unwrap();
+        assert!(display.external_reset);
+        let mut delay = TotalDelay(0);
+        display.hard_reset(&mut delay).unwrap();
+        assert_eq!(delay.0, 20);
+        assert!(display.is_sleeping());
+        assert!(!ST7306Builder::new().external_reset);
+        display.spi.done();
+        display.dc.done();
+        display.cs.done();
+    }
+
+    #[test]
+    fn set_pixel_bounds() {
+        let mut display = display(&[], &[], &[]);
+        for orientation in [Orientation::Portrait, Orientation::LandscapeSwapped] {
+            display.set_orientation(&orientation).unwrap();
+            let (width, height) = display.dimensions();
+            display.set_pixel(width - 1, height - 1, 0).unwrap();
+            display.set_pixel(0, 0, 0).unwrap();
+            for (x, y) in [(width, 0), (0, height), (u16::MAX, u16::MAX)] {
+                assert!(matches!(
+                    display.set_pixel(x, y, 0),
+                    Err(Error::OutOfBounds)
+                ));
+                assert!(matches!(
+                    display.set_pixel_on(x, y, true),
+                    Err(Error::OutOfBounds)
+                ));
+            }
+        }
+        done(display);
+    }
+
+    #[test]
+    fn set_pixel_clears_bit() {
+        let mut display = display(&[], &[], &[]);
+        display.framebuffer_mut()[0][0] = [0xFF; 3];
+        display.set_pixel(5, 1, 255).unwrap();
+        assert_eq!(display.framebuffer()[0][0], [0xFF, 0xEF, 0xFF]);
+        done(display);
+    }
+
+    #[test]
+    fn set_pixel_corners() {
+        let mut display = display(&[], &[], &[]);
+        display.set_pixel(0, 0, 0).unwrap();
+        display.set_pixel(23, 3, 0).unwrap();
+        assert_eq!(display.framebuffer()[0][0], [0x80, 0, 0]);
+        assert_eq!(display.framebuffer()[1][1], [0, 0, 0x01]);
+        done(display);
+    }
+
+    #[test]
+    fn fill_rect_aligned_and_fringe() {
+        let mut display = display(&[], &[], &[]);
+        display.fill_rect(0, 0, 13, 3, true);
+        // Whole cell, the fringe column and row, and the corner pixel
+        assert_eq!(display.framebuffer()[0][0], [0xFF; 3]);
+        assert_eq!(display.framebuffer()[0][1], [0xC0, 0, 0]);
+        assert_eq!(display.framebuffer()[1][0], [0xAA; 3]);
+        assert_eq!(display.framebuffer()[1][1], [0x80, 0, 0]);
+
+        // Clamped to the display
+        display.fill_rect(12, 0, 100, 100, true);
+        assert_eq!(display.framebuffer()[0][1], [0xFF; 3]);
+        assert_eq!(display.framebuffer()[1][1], [0xFF; 3]);
+        display.fill_rect(0, 0, 24, 4, false);
+        assert_eq!(display.framebuffer(), &[[[0; 3]; 2]; 2]);
+        done(display);
+    }
+
+    #[test]
+    fn flush_writes_framebuffer() {
+        let expected: Vec<u8> = vec![
+            0x80, 0x00, 0x00, 0x00, 0x00, 0x00, // row 0
+            0x00, 0x00, 0x00, 0x00, 0x00, 0x01, // row 1
+        ];
+        let mut display = display(
+            &[
+                SpiTransaction::write(vec![Instruction::RAMWR as u8]),
+                SpiTransaction::write(expected),
+            ],
+            &[
+                PinTransaction::set(State::Low),
+                PinTransaction::set(State::High),
+            ],
+            &[
+                PinTransaction::set(State::Low),
+                PinTransaction::set(State::High),
+                PinTransaction::set(State::Low),
+            ],
+        );
+        display.set_pixel(0, 0, 0).unwrap();
+        display.set_pixel(23, 3, 0).unwrap();
+        display.flush().unwrap();
+
+        // Nothing changed, so the second flush doesn't send anything
+        display.flush().unwrap();
+        done(display);
+    }
+
+    #[test]
+    fn flush_synced_waits_for_te() {
+        use State::{High, Low};
+
+        let mut display = Expect::default()
+            .command(Instruction::RAMWR, &[])
+            .data(&[0; 12])
+            .display();
+        let mut te = PinMock::new(&[]);
+        assert_eq!(display.flush_synced(&te), Err(Error::TearingDisabled));
+
+        display.te_enable = true;
+        let get = |state| PinTransaction::get(state);
+        te.update_expectations(&[get(High), get(Low), get(Low), get(High)]);
+        display.flush_synced(&te).unwrap();
+        te.done();
+        done(display);
+    }
+
+    #[test]
+    fn set_and_read_id() {
+        let mut display = Expect::default()
+            .command(Instruction::ID1SET, &[0x85])
+            .command(Instruction::ID2SET, &[0x02])
+            .command(Instruction::ID3SET, &[0x42])
+            // No dummy bit in single byte reads
+            .read(Instruction::RDID1, &[0x85])
+            .read(Instruction::RDID2, &[0x02])
+            .read(Instruction::RDID3, &[0x42])
+            .display();
+        display.set_id(0x85, 0x02, 0x42).unwrap();
+        assert_eq!(display.read_id(), Ok([0x85, 0x02, 0x42]));
+        done(display);
+    }
+
+    #[test]
+    fn nvm_read_and_program() {
+        use embedded_hal_mock::eh0::delay::NoopDelay;
+
+        let mut display = Expect::default()
+            .command(Instruction::NVMRDEN, &[NVM_ENABLE])
+            // The reply starts with a dummy bit
+            .read(Instruction::NVMRD, &[0x12, 0x34, 0x00])
+            .command(Instruction::NVMRDEN, &[NVM_DISABLE])
+            .command(Instruction::NVMPROM, &[NVM_ENABLE])
+            .command(Instruction::NVMCTRL2, &[0x24, 0x68])
+            .command(Instruction::NVMPROM, &[NVM_DISABLE])
+            .display();
+        assert_eq!(display.nvm_read::<2>(), Ok([0x24, 0x68]));
+        assert_eq!(display.nvm_read::<17>(), Err(Error::BufferSize));
+
+        let confirmation = NvmProgramConfirmation::i_understand_this_is_irreversible();
+        display
+            .nvm_program(&mut NoopDelay::new(), &[0x24, 0x68], confirmation)
+            .unwrap();
+        done(display);
+    }
+
+    #[cfg(feature = "graphics")]
+    #[test]
+    fn invert_region_partial_cells() {
+        use embedded_graphics::primitives::Rectangle;
+
+        for orientation in [Orientation::Portrait, Orientation::PortraitSwapped] {
+            let mut display = display(&[], &[], &[]);
+            display.set_orientation(&orientation).unwrap();
+            display.set_pixel_on(3, 3, true).unwrap();
+            let original = *display.framebuffer();
+
+            // Covers all of the second column of cells, parts of the first
+            let area = Rectangle::new(Point::new(2, 1), Size::new(30, 2));
+            display.invert_region(area);
+            let (width, height) = display.dimensions();
+            for y in 0..height {
+                for x in 0..width {
+                    let inside = area.contains(Point::new(x as i32, y as i32));
+                    let expected = inside != ((x, y) == (3, 3));
+                    assert_eq!(display.get_pixel(x, y), Some(expected), "{x},{y}");
+                }
+            }
+            display.invert_region(area);
+            assert_eq!(display.framebuffer(), &original);
+            done(display);
+        }
+    }
+
+    #[test]
+    fn rle_flush_sends_runs() {
+        let mut display = Expect::default()
+            .command(Instruction::RAMWR, &[])
+            .data(&[0; 9])
+            .more_data(&[0x80, 0, 0])
+            .display();
+        display.set_rle_flush(true);
+        display.set_pixel_on(12, 2, true).unwrap();
+        display.flush_full().unwrap();
+        done(display);
+    }
+
+    #[test]
+    fn set_brightness_levels() {
+        // VSHP and VSHN move together, level 128 keeps the defaults
+        let mut display = Expect::default()
+            .command(Instruction::VSHPCTRL, &[0x05; 4])
+            .command(Instruction::VSHNCTRL, &[0x21; 4])
+            .command(Instruction::VSHPCTRL, &[VSHP_DEFAULT; 4])
+            .command(Instruction::VSHNCTRL, &[VSHN_DEFAULT; 4])
+            .command(Instruction::VSHPCTRL, &[0x11; 4])
+            .command(Instruction::VSHNCTRL, &[0x2D; 4])
+            .display();
+        for level in [0, 128, 255] {
+            display.set_brightness(level).unwrap();
+        }
+        done(display);
+    }
+
+    #[test]
+    fn set_contrast_moves_voltages_apart() {
+        let voltage = VoltageConfig::default();
+        let mut display = Expect::default()
+            .command(Instruction::VSHPCTRL, &shift_voltage(voltage.vshp, 6))
+            .command(Instruction::VSLPCTRL, &shift_voltage(voltage.vslp, -6))
+            .display();
+        display.set_contrast(255).unwrap();
+        done(display);
+
+        assert_eq!(level_to_delta(0), -6);
+        assert_eq!(shift_voltage([0x23, 0, 0x3F, 1], -2), [0x21, 0, 0x3D, 0]);
+        assert_eq!(shift_voltage([0x3E, 0x3F, 0, 1], 2), [0x3F, 0x3F, 2, 3]);
+    }
+
+    #[test]
+    fn fps_config_round_trip() {
+        let mut valid = 0;
+        for byte in 0..=u8::MAX {
+            if let Some(fps) = FpsConfig::from_u8(byte) {
+                assert_eq!(fps.as_u8(), byte);
+                valid += 1;
+            }
+        }
+        // 2 HPM times 6 LPM frame rates
+        assert_eq!(valid, 12);
+        assert_eq!(FpsConfig::from_u8(0b00010110), None);
+        assert_eq!(FpsConfig::from_u8(0b00100010), None);
+    }
+
+    #[test]
+    fn fps_for_min_hz() {
+        let lpm = [
+            (0.0, Some(LpmFps::Quarter)),
+            (0.25, Some(LpmFps::Quarter)),
+            (0.3, Some(LpmFps::Half)),
+            (1.0, Some(LpmFps::One)),
+            (1.5, Some(LpmFps::Two)),
+            (3.0, Some(LpmFps::Four)),
+            (8.0, Some(LpmFps::Eight)),
+            (8.5, None),
+        ];
+        for (hz, fps) in lpm {
+            assert_eq!(LpmFps::for_min_hz(hz), fps, "{hz}Hz");
+        }
+        let hpm = [
+            (1.0, Some(HpmFps::Sixteen)),
+            (16.0, Some(HpmFps::Sixteen)),
+            (20.0, Some(HpmFps::ThirtyTwo)),
+            (33.0, None),
+        ];
+        for (hz, fps) in hpm {
+            assert_eq!(HpmFps::for_min_hz(hz), fps, "{hz}Hz");
+        }
+
+        let mut display = Expect::default()
+            .command(Instruction::FRCTRL, &[0b00010011])
+            .display();
+        display.set_fps_for_min_hz(PowerMode::Lpm, 2.0).unwrap();
+        assert!(matches!(
+            display.set_fps_for_min_hz(PowerMode::Hpm, 60.0),
+            Err(Error::OutOfBounds)
+        ));
+        assert_eq!(display.fps().hpm, HpmFps::ThirtyTwo);
+        done(display);
+    }
+
+    /// Adds up all delays
+    #[derive(Default)]
+    struct TotalDelay(u32);
+
+    impl DelayMs<u8> for TotalDelay {
+        fn delay_ms(&mut self, ms: u8) {
+            self.0 += ms as u32;
+        }
+    }
+
+    #[test]
+    fn init_twice() {
+        let mut display = display(&[], &[], &[]);
+        assert!(!display.is_initialized());
+        // Like after the first init
+        display.initialized = true;
+
+        // Nothing is sent the second time
+        let mut delay = TotalDelay(0);
+        assert_eq!(display.init(&mut delay), Err(Error::AlreadyInitialized));
+        assert_eq!(delay.0, 0);
+        assert!(display.is_initialized());
+        done(display);
+    }
+
+    #[test]
+    fn configurable_timings() {
+        let timings = Timings {
+            soft_reset_ms: 300,
+            sleep_out_ms: 20,
+            settle_frames: 1,
+            ..Timings::default()
+        };
+        let expect = Expect::default()
+            .command(Instruction::SWRESET, &[])
+            .command(Instruction::SLPOUT, &[])
+            .command(Instruction::LPM, &[]);
+        let mut display: ST7306Streaming<SpiMock, PinMock, PinMock, PinMock> = ST7306Builder::new()
+            .timings(timings)
+            .build(
+                SpiMock::new(&expect.spi),
+                PinMock::new(&expect.dc),
+                PinMock::new(&expect.cs),
+                PinMock::new(&[]),
+            )
+            .unwrap();
+        let mut delay = TotalDelay(0);
+        display.soft_reset(&mut delay).unwrap();
+        assert_eq!(delay.0, 300);
+        display.sleep_out(&mut delay).unwrap();
+        assert_eq!(delay.0, 320);
+        display.switch_mode(&mut delay, PowerMode::Lpm).unwrap();
+        assert_eq!(delay.0, 1320);
+        done(display);
+    }
+
+    #[test]
+    fn switch_mode_waits_for_frames() {
+        let mut display = Expect::default()
+            .command(Instruction::LPM, &[])
+            .command(Instruction::HPM, &[])
+            .display();
+        let mut delay = TotalDelay::default();
+        display.switch_mode(&mut delay, PowerMode::Lpm).unwrap();
+        // Two frames at 1Hz
+        assert_eq!(delay.0, 2000);
+        assert_eq!(display.frame_period_ms(), 1000);
+
+        let mut delay = TotalDelay::default();
+        display.switch_mode(&mut delay, PowerMode::Hpm).unwrap();
+        // Two frames at 32Hz
+        assert_eq!(delay.0, 64);
+        done(display);
+    }
+
+    #[test]
+    fn idle_wakes_and_goes_to_lpm() {
+        let mut display = Expect::default()
+            .command(Instruction::SLPOUT, &[])
+            .command(Instruction::DISPON, &[])
+            .command(Instruction::AUTOPWRCTRL, &[0xFF])
+            .command(Instruction::LPM, &[])
+            .command(Instruction::HPM, &[])
+            .command(Instruction::AUTOPWRCTRL, &[0x7F])
+            .display();
+        let mut delay = TotalDelay::default();
+        display.enter_idle(&mut delay).unwrap();
+        // Only the wake up, no slow frames
+        assert_eq!(delay.0, 100);
+        assert!(!display.is_sleeping());
+        assert!(display.is_on());
+        assert_eq!(display.power_mode(), PowerMode::Lpm);
+        assert!(display.register_cache().auto_power_down);
+        let mut delay = TotalDelay::default();
+        display.exit_idle(&mut delay).unwrap();
+        // Two frames at 32Hz
+        assert_eq!(delay.0, 64);
+        assert_eq!(display.power_mode(), PowerMode::Hpm);
+        assert!(!display.register_cache().auto_power_down);
+        done(display);
+    }
+
+    #[test]
+    fn panel_setting() {
+        let mut tuning = TuningConfig::default();
+        assert_eq!(tuning.panel_setting(), 0x29);
+        tuning.inversion = Inversion::Column;
+        tuning.frame_interval = 0b11;
+        assert_eq!(tuning.panel_setting(), 0b00001101);
+
+        let mut display = Expect::default()
+            .command(Instruction::PNLSET, &[0b01101001])
+            .display();
+        display.set_inversion(Inversion::Frame).unwrap();
+        assert_eq!(display.tuning.inversion, Inversion::Frame);
+        done(display);
+    }
+
+    #[test]
+    fn nvm_load_ctrl() {
+        assert_eq!(NvmLoadCtrl::default().as_bytes(), [0b10001, 0]);
+        let all = NvmLoadCtrl {
+            vs_en: true,
+            id_en: true,
+            load_by_timer: false,
+            load_by_slpout: true,
+        };
+        assert_eq!(all.as_bytes(), [0x17, 0x02]);
+    }
+
+    #[test]
+    fn enable_extended_commands() {
+        let mut display = Expect::default()
+            .command(Instruction::EXTBCTRL, &[EXTB_ENABLE])
+            .command(Instruction::EXTBCTRL, &[EXTB_DISABLE])
+            .display();
+        display.enable_extended_commands(true).unwrap();
+        display.enable_extended_commands(false).unwrap();
+        done(display);
+    }
+
+    #[test]
+    fn standby_keeps_ram() {
+        let mut display = Expect::default()
+            .command(Instruction::DISPOFF, &[])
+            .command(Instruction::DISPON, &[])
+            .display();
+        display.dirty = None;
+        display.sleeping = false;
+        display.standby().unwrap();
+        assert!(!display.is_on());
+        assert!(!display.is_sleeping());
+        display.wake().unwrap();
+        assert!(display.is_on());
+        assert!(!display.needs_flush());
+        done(display);
+    }
+
+    #[test]
+    fn flush_byte_count() {
+        let mut display = Expect::default()
+            .command(Instruction::RAMWR, &[])
+            .data(&[0; 12])
+            .display();
+        assert_eq!(display.flush_byte_count(), 13);
+        display.ram_window = None;
+        assert_eq!(display.flush_byte_count(), 19);
+        display.ram_window = Some(display.addr_window);
+        display.flush().unwrap();
+        assert_eq!(display.flush_byte_count(), 0);
+        done(display);
+
+        // Large enough for a partial flush of one cell
+        let mut display = ST7306Builder::new()
+            .dimensions(300, 400)
+            .build::<_, _, _, _, 25, 200>(
+                SpiMock::new(&[]),
+                PinMock::new(&[]),
+                PinMock::new(&[]),
+                PinMock::new(&[]),
+            )
+            .unwrap();
+        display.dirty = None;
+        display.set_pixel(0, 0, 0).unwrap();
+        assert_eq!(display.flush_byte_count(), 2 * 6 + 1 + 3);
+        done(display);
+    }
+
+    #[test]
+    fn flush_step() {
+        let mut display = Expect::default()
+            .command(Instruction::RAMWR, &[])
+            .data(&[0x80, 0, 0, 0, 0, 0])
+            .command(Instruction::WRMEMC, &[])
+            .data(&[0, 0, 0, 0, 0, 0x01])
+            .display();
+        display.set_pixel(0, 0, 0).unwrap();
+        display.set_pixel(23, 3, 0).unwrap();
+        let progress = display.flush_step(1).unwrap();
+        assert_eq!(
+            progress,
+            FlushProgress {
+                rows_sent: 1,
+                rows_total: 2
+            }
+        );
+        assert!(display.needs_flush());
+        assert!(display.flush_step(1).unwrap().is_done());
+        assert!(!display.needs_flush());
+        done(display);
+    }
+
+    #[test]
+    fn set_oscillator() {
+        let mut display = Expect::default()
+            .command(Instruction::OSCSET, &[0x26, 0xE9])
+            .display();
+        assert_eq!(display.tuning.oscillator, [0xA6, 0xE9]);
+        display.set_oscillator([0x26, 0xE9]).unwrap();
+        assert_eq!(display.tuning.oscillator, [0x26, 0xE9]);
+        done(display);
+    }
+
+    #[test]
+    fn refresh_once() {
+        let mut display = Expect::default()
+            .command(Instruction::HPM, &[])
+            .command(Instruction::RAMWR, &[])
+            .data(&[0; 12])
+            .command(Instruction::LPM, &[])
+            .display();
+        display.power_mode = PowerMode::Lpm;
+        let mut delay = TotalDelay(0);
+        display.refresh_once(&mut delay).unwrap();
+        assert_eq!(display.power_mode(), PowerMode::Lpm);
+        assert!(!display.needs_flush());
+        // Two frames after switching and two after flushing, all in HPM
+        display.power_mode = PowerMode::Hpm;
+        assert_eq!(delay.0, 4 * display.frame_period_ms() as u32);
+        done(display);
+    }
+
+    #[test]
+    fn set_first_gate() {
+        let mut display = Expect::default()
+            .command(Instruction::FSTCOM, &[4])
+            .display();
+        assert_eq!(display.first_gate, None);
+        display.set_first_gate(4).unwrap();
+        // Kept for the next init
+        assert_eq!(display.first_gate, Some(4));
+        done(display);
+    }
+
+    #[test]
+    fn gate_timing() {
+        let voltage = VoltageConfig::default();
+        let tuning = TuningConfig::default();
+        let expect = Expect::default()
+            .command(Instruction::NVMLOADCTRL, &[0b10001, 0])
+            .command(Instruction::BSTEN, &[0x01])
+            .command(Instruction::GCTRL, &voltage.gate)
+            .command(Instruction::VSHPCTRL, &voltage.vshp)
+            .command(Instruction::VSLPCTRL, &voltage.vslp)
+            .command(Instruction::VSHNCTRL, &voltage.vshn)
+            .command(Instruction::VSLNCTRL, &voltage.vsln)
+            .command(Instruction::GTCON, &[0x32, 0x03, 0x1F])
+            .command(Instruction::OSCSET, &tuning.oscillator)
+            .command(Instruction::FRCTRL, &[0x12])
+            .command(Instruction::GTUPEQH, &tuning.hpm_eq)
+            .command(Instruction::GTUPEQL, &tuning.lpm_eq)
+            .command(Instruction::SOUEQ, &[tuning.source_eq])
+            .command(Instruction::GATESET, &[100])
+            .command(Instruction::GTCON, &[0x30, 0x03, 0x1F]);
+        let mut display: ST7306Streaming<SpiMock, PinMock, PinMock, PinMock> = ST7306Builder::new()
+            .gate_timing([0x32, 0x03, 0x1F])
+            .build(
+                SpiMock::new(&expect.spi),
+                PinMock::new(&expect.dc),
+                PinMock::new(&expect.cs),
+                PinMock::new(&[]),
+            )
+            .unwrap();
+        display.load_config().unwrap();
+        display.set_gate_timing([0x30, 0x03, 0x1F]).unwrap();
+        assert_eq!(display.gate_timing, Some([0x30, 0x03, 0x1F]));
+        done(display);
+    }
+
+    #[test]
+    fn set_gamma_voltage() {
+        let mut display = Expect::default()
+            .command(Instruction::VSIKCTRL, &[0x11, 0x22, 0x33, 0x44])
+            .display();
+        assert_eq!(display.gamma_voltage, None);
+        display.set_gamma_voltage([0x11, 0x22, 0x33, 0x44]).unwrap();
+        // Kept for the next init
+        assert_eq!(display.gamma_voltage, Some([0x11, 0x22, 0x33, 0x44]));
+        done(display);
+    }
+
+    #[test]
+    fn set_tear_scanline() {
+        let mut display = Expect::default()
+            .command(Instruction::TESCAN, &[4])
+            .display();
+        // Only 4px high, so there's one gate line
+        assert!(matches!(
+            display.set_tear_scanline(1),
+            Err(Error::OutOfBounds)
+        ));
+        display.gate_lines = 5;
+        display.set_tear_scanline(4).unwrap();
+        assert_eq!(display.tear_scanline(), 4);
+        done(display);
+    }
+
+    #[cfg(feature = "graphics")]
+    #[test]
+    fn draw_image() {
+        let mut display = display(&[], &[], &[]);
+        // 3x2 pixels, black, white and red on top, white, black and white
+        // below
+        let data = [
+            0x00, 0x00, 0xFF, 0xFF, 0xF8, 0x00, //
+            0xFF, 0xFF, 0x00, 0x00, 0xFF, 0xFF,
+        ];
+        let image = ImageRaw::<Rgb565>::new(&data, 3);
+        display.draw_image(&image, Point::new(22, 2)).unwrap();
+        assert_eq!(display.get_pixel(22, 2), Some(true));
+        assert_eq!(display.get_pixel(23, 2), Some(false));
+        assert_eq!(display.get_pixel(22, 3), Some(false));
+        assert_eq!(display.get_pixel(23, 3), Some(true));
+        done(display);
+    }
+
+    #[cfg(feature = "graphics")]
+    #[test]
+    fn mono_colors() {
+        assert!(col_to_bright(mono::BLACK) < BLACK_THRESHOLD);
+        assert!(col_to_bright(mono::WHITE) >= BLACK_THRESHOLD);
+        assert_eq!(mono::mono_color(true), mono::BLACK);
+        assert_eq!(mono::mono_color(false), mono::WHITE);
+    }
+
+    #[cfg(feature = "graphics")]
+    #[test]
+    fn black_threshold_is_consistent() {
+        let colors = [
+            (Rgb565::BLACK, true),
+            // Just below and above the threshold
+            (Rgb565::new(15, 31, 15), true),
+            (Rgb565::new(16, 33, 16), false),
+            (Rgb565::RED, true),
+            (Rgb565::WHITE, false),
+        ];
+        let mut expect = Expect::default();
+        for (_, black) in colors {
+            expect = expect
+                .command(Instruction::DISPOFF, &[])
+                .command(Instruction::CLRAM, &[if black { 0x8F } else { 0xCF }])
+                .command(Instruction::DISPON, &[]);
+        }
+        let mut display = expect.display();
+        for (color, black) in colors {
+            display.set_pixel(0, 0, display.luminance(color)).unwrap();
+            display.draw_iter([Pixel(Point::new(1, 0), color)]).unwrap();
+            display
+                .fill_solid(&Rectangle::new(Point::new(2, 0), Size::new(1, 1)), color)
+                .unwrap();
+            for x in 0..3 {
+                assert_eq!(display.get_pixel(x, 0), Some(black), "{color:?}");
+            }
+            display.clear(color).unwrap();
+            assert_eq!(display.get_pixel(0, 0), Some(black), "{color:?}");
+        }
+        done(display);
+    }
+
+    #[cfg(feature = "graphics")]
+    #[test]
+    fn clear_uses_clram_for_both_colors() {
+        let mut display = Expect::default()
+            .command(Instruction::DISPOFF, &[])
+            .command(Instruction::CLRAM, &[0xCF])
+            .command(Instruction::DISPON, &[])
+            .command(Instruction::DISPOFF, &[])
+            .command(Instruction::CLRAM, &[0x8F])
+            .command(Instruction::DISPON, &[])
+            .display();
+        display.clear(Rgb565::WHITE).unwrap();
+        assert!(!display.needs_flush());
+        display.clear(Rgb565::BLACK).unwrap();
+        assert!(!display.needs_flush());
+        assert_eq!(display.get_pixel(23, 3), Some(true));
+        done(display);
+    }
+
+    #[test]
+    fn flush_in_chunks() {
+        let mut display = Expect::default()
+            .command(Instruction::RAMWR, &[])
+            .data(&[0x80, 0, 0, 0, 0])
+            .more_data(&[0; 5])
+            .more_data(&[0, 0])
+            .display();
+        display.max_transfer = 5;
+        display.set_pixel_on(0, 0, true).unwrap();
+        display.flush_full().unwrap();
+        done(display);
+    }
+
+    #[test]
+    fn write_ram_continue() {
+        let mut display = Expect::default()
+            .command(Instruction::WRMEMC, &[])
+            .data(&[1, 2, 3])
+            .display();
+        assert!(matches!(
+            display.write_ram_continue(&[1, 2]),
+            Err(Error::BufferSize)
+        ));
+        display.write_ram_continue(&[1, 2, 3]).unwrap();
+        done(display);
+    }
+
+    #[test]
+    fn write_ram_iter_batches() {
+        let cells = || (0..20u8).map(|i| (i, 0, 0xFF));
+        let bytes: Vec<u8> = cells().flat_map(|(a, b, c)| [a, b, c]).collect();
+        let mut display = Expect::default()
+            .command(Instruction::RAMWR, &[])
+            .data(&bytes[..48])
+            .more_data(&bytes[48..])
+            .display();
+        display.write_command(Instruction::RAMWR, &[]).unwrap();
+        display.start_data().unwrap();
+        display.write_ram_iter(cells()).unwrap();
+        done(display);
+    }
+
+    #[cfg(feature = "double-buffer")]
+    #[test]
+    fn present_flushes_the_difference() {
+        // On such a small display a partial flush isn't worth it
+        let mut display = Expect::default()
+            .command(Instruction::RAMWR, &[])
+            .data(&[0, 0, 0, 0, 0, 0, 0, 0, 0, 0x80, 0, 0])
+            .display();
+        display.dirty = None;
+        display.back_buffer_mut()[1][1] = [0x80, 0, 0];
+        display.present().unwrap();
+        assert_eq!(display.get_pixel(12, 2), Some(true));
+        assert_eq!(display.back_buffer_mut()[1][1], [0; 3]);
+        done(display);
+    }
+
+    #[test]
+    fn flush_full_restores_address_window() {
+        let mut display = Expect::default()
+            .command(Instruction::CASET, &[1, 1])
+            .command(Instruction::RASET, &[0, 1])
+            .command(Instruction::CASET, &[0, 1])
+            .command(Instruction::RASET, &[0, 1])
+            .command(Instruction::RAMWR, &[])
+            .data(&[0; 12])
+            .display();
+        assert!(matches!(
+            display.set_address_window(0, 2, 0, 1),
+            Err(Error::OutOfBounds)
+        ));
+        display.set_address_window(1, 1, 0, 1).unwrap();
+        display.flush_full().unwrap();
+        done(display);
+    }
+
+    #[cfg(feature = "row-hash")]
+    #[test]
+    fn flush_rows_skips_unchanged_rows() {
+        let mut display = Expect::default()
+            // First flush sends everything
+            .command(Instruction::RASET, &[0, 1])
+            .command(Instruction::RAMWR, &[])
+            .data(&[0; 6])
+            .more_data(&[0; 6])
+            .command(Instruction::CASET, &[0, 1])
+            .command(Instruction::RASET, &[0, 1])
+            // Then only the changed row
+            .command(Instruction::RASET, &[1, 1])
+            .command(Instruction::RAMWR, &[])
+            .data(&[0x80, 0, 0, 0, 0, 0])
+            .command(Instruction::CASET, &[0, 1])
+            .command(Instruction::RASET, &[0, 1])
+            .display();
+
+        display.flush_rows().unwrap();
+        display.set_pixel_on(0, 2, true).unwrap();
+        display.flush_rows().unwrap();
+        assert!(!display.needs_flush());
+
+        // Nothing changed, no SPI traffic at all
+        display.set_pixel_on(0, 2, false).unwrap();
+        display.set_pixel_on(0, 2, true).unwrap();
+        display.flush_rows().unwrap();
+        done(display);
+    }
+
+    #[test]
+    fn size_macro() {
+        assert_eq!((cols_for(300), rows_for(400)), (25, 200));
+        assert_eq!((cols_for(13), rows_for(3)), (2, 2));
+
+        let display: st7306_for!(24, 4) = ST7306Builder::new()
+            .dimensions(24, 4)
+            .build(
+                SpiMock::new(&[]),
+                PinMock::new(&[]),
+                PinMock::new(&[]),
+                PinMock::new(&[]),
+            )
+            .unwrap();
+        let display: st7306_for!(SpiMock, PinMock, PinMock, PinMock, 24, 4) = display;
+        assert_eq!(display.framebuffer().len(), 2);
+        assert_eq!(display.framebuffer()[0].len(), 2);
+        done(display);
+    }
+
+    #[test]
+    fn clear_white_no_blank_keeps_display_on() {
+        // Only RAMWR and the white frame, no DISPOFF/DISPON or CLRAM
+        let mut display = display(
+            &[
+                SpiTransaction::write(vec![Instruction::RAMWR as u8]),
+                SpiTransaction::write(vec![0; 12]),
+            ],
+            &[
+                PinTransaction::set(State::Low),
+                PinTransaction::set(State::High),
+            ],
+            &[
+                PinTransaction::set(State::Low),
+                PinTransaction::set(State::High),
+                PinTransaction::set(State::Low),
+            ],
+        );
+        display.framebuffer_mut()[1][0] = [0xFF; 3];
+        display.clear_white_no_blank().unwrap();
+        assert_eq!(display.framebuffer(), &[[[0; 3]; 2]; 2]);
+        done(display);
+    }
+
+    #[test]
+    fn write_frame_without_framebuffer() {
+        use State::{High, Low};
+
+        let frame: Vec<u8> = (0..12).collect();
+        let mut display: ST7306Streaming<_, _, _, _> = ST7306Builder::new()
+            .dimensions(24, 4)
+            .offset(1, 2)
+            .build(
+                SpiMock::new(&[
+                    SpiTransaction::write(vec![Instruction::CASET as u8]),
+                    SpiTransaction::write(vec![1, 2]),
+                    SpiTransaction::write(vec![Instruction::RASET as u8]),
+                    SpiTransaction::write(vec![2, 3]),
+                    SpiTransaction::write(vec![Instruction::RAMWR as u8]),
+                    SpiTransaction::write(frame.clone()),
+                ]),
+                PinMock::new(&pin_states(&[Low, High, Low, High, Low, High])),
+                PinMock::new(&pin_states(&[
+                    Low, Low, High, Low, Low, High, Low, High, Low,
+                ])),
+                PinMock::new(&[]),
+            )
+            .unwrap();
+        assert_eq!(display.validate_config(), Ok(()));
+        assert_eq!(display.dimensions(), (0, 0));
+        assert!(!display.needs_flush());
+        assert_eq!(display.set_pixel_on(0, 0, true), Err(Error::OutOfBounds));
+
+        assert_eq!(display.write_frame(&frame[..9]), Err(Error::BufferSize));
+        display.write_frame(&frame).unwrap();
+
+        display.spi.done();
+        display.dc.done();
+        display.cs.done();
+        display.rst.done();
+    }
+
+    /// Display that expects a single full flush of `data`
+    fn flushing_display(data: Vec<u8>) -> MockDisplay {
+        use State::{High, Low};
+
+        display(
+            &[
+                SpiTransaction::write(vec![Instruction::RAMWR as u8]),
+                SpiTransaction::write(data),
+            ],
+            &pin_states(&[Low, High]),
+            &pin_states(&[Low, High, Low]),
+        )
+    }
+
+    #[test]
+    fn test_pattern_checkerboard() {
+        let mut display = flushing_display(vec![
+            0xFF, 0xFF, 0xFF, 0, 0, 0, // row 0
+            0, 0, 0, 0xFF, 0xFF, 0xFF, // row 1
+        ]);
+        display
+            .draw_test_pattern(TestPattern::Checkerboard)
+            .unwrap();
+        assert_eq!(display.get_pixel(11, 1), Some(true));
+        assert_eq!(display.get_pixel(12, 1), Some(false));
+        done(display);
+    }
+
+    #[test]
+    fn test_pattern_stripes() {
+        let mut display = flushing_display(vec![0b11001100; 12]);
+        display
+            .draw_test_pattern(TestPattern::VerticalStripes)
+            .unwrap();
+        for x in 0..24 {
+            assert_eq!(display.get_pixel(x, 3), Some(x % 2 == 0));
+        }
+        done(display);
+
+        let mut display = flushing_display(vec![0b10101010; 12]);
+        display
+            .draw_test_pattern(TestPattern::HorizontalStripes)
+            .unwrap();
+        for y in 0..4 {
+            assert_eq!(display.get_pixel(7, y), Some(y % 2 == 0));
+        }
+        done(display);
+    }
+
+    #[test]
+    fn needs_flush_only_after_changes() {
+        let mut display = display(
+            &[
+                SpiTransaction::write(vec![Instruction::RAMWR as u8]),
+                SpiTransaction::write(vec![0; 12]),
+            ],
+            &[
+                PinTransaction::set(State::Low),
+                PinTransaction::set(State::High),
+            ],
+            &[
+                PinTransaction::set(State::Low),
+                PinTransaction::set(State::High),
+                PinTransaction::set(State::Low),
+            ],
+        );
+        // The RAM content is unknown at the start
+        assert!(display.needs_flush());
+        display.flush().unwrap();
+        assert!(!display.needs_flush());
+
+        // Already white
+        display.set_pixel_on(3, 1, false).unwrap();
+        assert!(!display.needs_flush());
+
+        display.set_pixel_on(3, 1, true).unwrap();
+        assert!(display.needs_flush());
+        done(display);
+    }
+
+    #[test]
+    fn register_cache_mirrors_writes() {
+        let expect = Expect::default()
+            .command(Instruction::MADCTL, &[0b11001100])
+            .command(Instruction::INVON, &[]);
+        let mut display = expect.display();
+        let before = display.register_cache();
+        assert_eq!(before.madctl, MADCTL_DEFAULT);
+        assert_eq!(before.frctrl, 0x12);
+        assert!(!before.inverted);
+
+        display
+            .set_madctl(MadctlFlags::from_bits(0b11001100))
+            .unwrap();
+        display.invert_screen(true).unwrap();
+        let after = display.register_cache();
+        assert_eq!(after.madctl, 0b11001100);
+        assert!(after.inverted);
+        assert_eq!(
+            RegisterCache {
+                madctl: before.madctl,
+                inverted: false,
+                ..after
+            },
+            before
+        );
+        done(display);
+    }
+
+    #[test]
+    fn set_mirror_xy() {
+        let mut display = Expect::default()
+            .command(Instruction::MADCTL, &[0b00001000])
+            .command(Instruction::MADCTL, &[0b10001000])
+            .command(Instruction::MADCTL, &[0b11001000])
+            .display();
+        display.set_pixel(0, 0, 0).unwrap();
+        display.dirty = None;
+
+        // The pixels already in the framebuffer move inside of their cell
+        display.set_mirror_x(true).unwrap();
+        assert_eq!(display.mirrored(), (true, false));
+        assert_eq!(display.framebuffer[0][0], [0x00, 0x00, 0x02]);
+        assert!(display.dirty.is_some());
+        assert_eq!(display.get_pixel(0, 0), Some(true));
+
+        display.set_pixel(13, 1, 0).unwrap();
+        assert_eq!(display.framebuffer[0][1], [0x00, 0x00, 0x04]);
+
+        display.set_mirror_y(true).unwrap();
+        assert_eq!(display.mirrored(), (true, true));
+        assert_eq!(display.framebuffer[0][0], [0x00, 0x00, 0x01]);
+        assert_eq!(display.framebuffer[0][1], [0x00, 0x00, 0x08]);
+        assert_eq!(display.get_pixel(0, 0), Some(true));
+        assert_eq!(display.get_pixel(13, 1), Some(true));
+
+        display.set_mirror_x(false).unwrap();
+        assert_eq!(display.mirrored(), (false, true));
+        assert_eq!(display.framebuffer[0][0], [0x40, 0x00, 0x00]);
+        done(display);
+
+        // Gray4 pixels are two bits wide, six of them in a row of a cell
+        assert_eq!(mirror_cell([0xC0, 0, 0], 2, true, false), [0, 0, 0x0C]);
+        assert_eq!(mirror_cell([0xC0, 0, 0], 2, false, true), [0x30, 0, 0]);
+    }
+
+    #[test]
+    fn set_madctl_writes_flags() {
+        let mut display = display(
+            &[
+                SpiTransaction::write(vec![Instruction::MADCTL as u8]),
+                SpiTransaction::write(vec![0b11001100]),
+            ],
+            &[
+                PinTransaction::set(State::Low),
+                PinTransaction::set(State::High),
+            ],
+            &[
+                PinTransaction::set(State::Low),
+                PinTransaction::set(State::Low),
+                PinTransaction::set(State::High),
+            ],
+        );
+        assert_eq!(display.madctl().bits(), MADCTL_DEFAULT);
+        let flags = MadctlFlags {
+            my: true,
+            gs: true,
+            ..MadctlFlags::default()
+        };
+        display.set_madctl(flags).unwrap();
+        assert_eq!(display.madctl(), flags);
+        done(display);
+    }
+
+    #[cfg(feature = "graphics")]
+    #[test]
+    fn binary_color() {
+        use embedded_graphics::pixelcolor::BinaryColor;
+
+        let mut display = display(&[], &[], &[]);
+        display.framebuffer_mut()[1][1] = [0xFF; 3];
+        let mut target = display.binary();
+        assert_eq!(target.size(), Size::new(24, 4));
+        target
+            .draw_iter([
+                Pixel(Point::new(0, 0), BinaryColor::On),
+                Pixel(Point::new(23, 3), BinaryColor::Off),
+                // Outside of the display
+                Pixel(Point::new(24, 0), BinaryColor::On),
+            ])
+            .unwrap();
+        assert_eq!(display.framebuffer()[0][0], [0x80, 0, 0]);
+        assert_eq!(display.framebuffer()[1][1], [0xFF, 0xFF, 0xFE]);
+        done(display);
+    }
+
+    #[cfg(feature = "graphics")]
+    #[test]
+    fn gray8_dithers() {
+        let mut display = display(&[], &[], &[]);
+        let area = Rectangle::new(Point::zero(), Size::new(4, 4));
+        display.gray8().fill_solid(&area, Gray8::new(124)).unwrap();
+        let black = area
+            .points()
+            .filter(|p| display.get_pixel(p.x as u16, p.y as u16) == Some(true))
+            .count();
+        assert_eq!(black, 8);
+
+        display.gray8().fill_solid(&area, Gray8::BLACK).unwrap();
+        assert!(area
+            .points()
+            .all(|p| display.get_pixel(p.x as u16, p.y as u16) == Some(true)));
+        display.gray8().fill_solid(&area, Gray8::WHITE).unwrap();
+        assert!(area
+            .points()
+            .all(|p| display.get_pixel(p.x as u16, p.y as u16) == Some(false)));
+        done(display);
+    }
+
+    #[cfg(feature = "graphics")]
+    #[test]
+    fn flush_area() {
+        let mut display = Expect::default()
+            .command(Instruction::CASET, &[0x01, 0x01])
+            .command(Instruction::RASET, &[0x00, 0x01])
+            .command(Instruction::RAMWR, &[])
+            .data(&[0x80, 0, 0])
+            .more_data(&[0, 0, 0])
+            .command(Instruction::CASET, &[0x00, 0x01])
+            .command(Instruction::RASET, &[0x00, 0x01])
+            .display();
+        display.dirty = None;
+        display.set_pixel(12, 0, 0).unwrap();
+        // Widened to the whole second column, clamped to the display
+        display
+            .flush_area(Rectangle::new(Point::new(13, 1), Size::new(100, 100)))
+            .unwrap();
+        assert!(!display.needs_flush());
+        display
+            .flush_area(Rectangle::new(Point::new(30, 0), Size::new(1, 1)))
+            .unwrap();
+        done(display);
+    }
+
+    #[cfg(feature = "graphics")]
+    #[test]
+    fn draw_pixels_binary() {
+        let mut display = display(&[], &[], &[]);
+        // Dithering doesn't apply to binary colors
+        display.set_dither_mode(DitherMode::Bayer4x4);
+        let pixels = [
+            Pixel(Point::new(0, 0), BinaryColor::On),
+            Pixel(Point::new(1, 0), BinaryColor::Off),
+            Pixel(Point::new(23, 3), BinaryColor::On),
+            Pixel(Point::new(24, 0), BinaryColor::On),
+            Pixel(Point::new(-1, 0), BinaryColor::On),
+        ];
+        display.draw_pixels_binary(pixels, false).unwrap();
+        assert_eq!(display.framebuffer()[0][0], [0x80, 0, 0]);
+        assert_eq!(display.framebuffer()[1][1], [0, 0, 0x01]);
+        assert_eq!(display.framebuffer()[0][1], [0; 3]);
+        done(display);
+    }
+
+    #[cfg(feature = "graphics")]
+    #[test]
+    fn bayer_dither() {
+        let mut display = display(&[], &[], &[]);
+        let gray = Rgb565::new(15, 31, 15);
+        display
+            .fill_solid(&Rectangle::new(Point::zero(), Size::new(4, 4)), gray)
+            .unwrap();
+        // Without dithering this gray is just below the threshold
+        assert_eq!(display.get_pixel(0, 0), Some(true));
+
+        display.set_dither_mode(DitherMode::Bayer4x4);
+        display
+            .fill_solid(&Rectangle::new(Point::zero(), Size::new(4, 4)), gray)
+            .unwrap();
+        let black = (0..4)
+            .flat_map(|y| (0..4).map(move |x| (x, y)))
+            .filter(|&(x, y)| display.get_pixel(x, y) == Some(true))
+            .count();
+        // About half of the pixels
+        assert_eq!(black, 8);
+        assert_eq!(display.get_pixel(0, 0), Some(false));
+        assert_eq!(display.get_pixel(1, 0), Some(true));
+
+        // Black and white stay solid
+        display.clear_buffer(false);
+        display
+            .fill_solid(
+                &Rectangle::new(Point::zero(), Size::new(4, 4)),
+                Rgb565::BLACK,
+            )
+            .unwrap();
+        assert!((0..4).all(|y| (0..4).all(|x| display.get_pixel(x, y) == Some(true))));
+        done(display);
+    }
+
+    #[cfg(feature = "dither")]
+    #[test]
+    fn fill_gradient_endpoints() {
+        // Wider than 128px, where the ramp used to overflow
+        for (from, to) in [(0, 255), (255, 0)] {
+            let mut display = ST7306Builder::new()
+                .dimensions(300, 2)
+                .build::<_, _, _, _, 25, 1>(
+                    SpiMock::new(&[]),
+                    PinMock::new(&[]),
+                    PinMock::new(&[]),
+                    PinMock::new(&[]),
+                )
+                .unwrap();
+            let area = Rectangle::new(Point::zero(), Size::new(300, 2));
+            display.fill_gradient(area, from, to, Axis::Horizontal);
+
+            // The first and last cells are solid
+            let start_black = from < BLACK_THRESHOLD;
+            for y in 0..2 {
+                for x in 0..12 {
+                    assert_eq!(display.get_pixel(x, y), Some(start_black));
+                    assert_eq!(display.get_pixel(299 - x, y), Some(!start_black));
+                }
+            }
+            done(display);
+        }
+    }
+
+    #[test]
+    fn flush_if_changed() {
+        let mut pixel = [0; 12];
+        pixel[0] = 0x80;
+        let mut display = Expect::default()
+            .command(Instruction::RAMWR, &[])
+            .data(&pixel)
+            .display();
+
+        // No change, nothing is sent
+        let mut last_seen = display.generation();
+        assert_eq!(display.flush_if_changed(&mut last_seen), Ok(false));
+
+        display.set_pixel(0, 0, 0).unwrap();
+        assert_ne!(display.generation(), last_seen);
+        assert_eq!(display.flush_if_changed(&mut last_seen), Ok(true));
+        assert_eq!(last_seen, display.generation());
+
+        // Setting a pixel to the color it already has isn't a change
+        display.set_pixel(0, 0, 0).unwrap();
+        assert_eq!(display.generation(), last_seen);
+        assert_eq!(display.flush_if_changed(&mut last_seen), Ok(false));
+        done(display);
+    }
+
+    #[test]
+    fn clear_ram_keep_on() {
+        // Only CLRAM, without DISPOFF and DISPON around it
+        let mut display = Expect::default()
+            .command(Instruction::CLRAM, &[0xCF])
+            .display();
+        display.clear_ram_keep_on().unwrap();
+        done(display);
+    }
+
+    #[test]
+    fn dump_ascii() {
+        use std::string::String;
+
+        let mut display = display(&[], &[], &[]);
+        for (x, y) in [(0, 0), (5, 1), (23, 3)] {
+            display.set_pixel_on(x, y, true).unwrap();
+        }
+        let mut out = String::new();
+        display.dump_ascii(&mut out).unwrap();
+        let expected = [
+            "#                       ",
+            "     #                  ",
+            "                        ",
+            "                       #",
+        ];
+        assert_eq!(out.lines().collect::<Vec<_>>(), expected);
+        done(display);
+
+        // 120px are scaled down by 2, a character is black if any of its
+        // 2x2 pixels is
+        let mut display = ST7306Builder::new()
+            .dimensions(120, 4)
+            .build::<_, _, _, _, 10, 2>(
+                SpiMock::new(&[]),
+                PinMock::new(&[]),
+                PinMock::new(&[]),
+                PinMock::new(&[]),
+            )
+            .unwrap();
+        display.set_pixel_on(3, 1, true).unwrap();
+        display.set_pixel_on(119, 3, true).unwrap();
+        let mut out = String::new();
+        display.dump_ascii(&mut out).unwrap();
+        let lines: Vec<_> = out.lines().collect();
+        assert_eq!(lines.len(), 2);
+        assert_eq!(lines[0], std::format!(" #{}", " ".repeat(58)));
+        assert_eq!(lines[1], std::format!("{}#", " ".repeat(59)));
+        done(display);
+    }
+
+    #[test]
+    fn hmsb_round_trip() {
+        // 36px wide, so each row is padded by 4 bits to 5 bytes
+        let mut display = ST7306Builder::new()
+            .dimensions(36, 2)
+            .build::<_, _, _, _, 3, 1>(
+                SpiMock::new(&[]),
+                PinMock::new(&[]),
+                PinMock::new(&[]),
+                PinMock::new(&[]),
+            )
+            .unwrap();
+        let image = [
+            0xA5, 0x3C, 0xFF, 0x01, 0x90, //
+            0x00, 0x81, 0x42, 0x18, 0x60,
+        ];
+        display.import_hmsb(&image, 36, 2).unwrap();
+        assert_eq!(display.get_pixel(0, 0), Some(true));
+        assert_eq!(display.get_pixel(1, 0), Some(false));
+        assert_eq!(display.get_pixel(32, 0), Some(true));
+        assert_eq!(display.get_pixel(34, 0), Some(false));
+        assert_eq!(display.get_pixel(35, 0), Some(true));
+        assert_eq!(display.get_pixel(34, 1), Some(true));
+
+        let mut exported = [0xFF; 10];
+        display.export_hmsb(&mut exported).unwrap();
+        assert_eq!(exported, image);
+
+        // Too small for the dimensions
+        assert_eq!(
+            display.import_hmsb(&image[..9], 36, 2),
+            Err(Error::BufferSize)
+        );
+        assert_eq!(
+            display.export_hmsb(&mut exported[..9]),
+            Err(Error::BufferSize)
+        );
+        done(display);
+    }
+
+    #[cfg(feature = "graphics")]
+    #[test]
+    fn draw_sprite_masked() {
+        let mut display = display(&[], &[], &[]);
+        display.fill_rect(0, 0, 24, 4, true);
+
+        // A white 4x2 sprite, one pixel off the left edge
+        let bitmap = [0x00, 0x00];
+        let mask = [0b10100000, 0b01010000];
+        display
+            .draw_sprite_masked(&bitmap, &mask, 4, Point::new(-1, 0))
+            .unwrap();
+        // Masked out pixels keep the background
+        assert_eq!(display.get_pixel(0, 0), Some(true));
+        assert_eq!(display.get_pixel(1, 0), Some(false));
+        assert_eq!(display.get_pixel(2, 0), Some(true));
+        assert_eq!(display.get_pixel(0, 1), Some(false));
+        assert_eq!(display.get_pixel(1, 1), Some(true));
+        assert_eq!(display.get_pixel(2, 1), Some(false));
+        // Everything else is untouched
+        assert_eq!(display.get_pixel(3, 0), Some(true));
+        assert_eq!(display.get_pixel(0, 2), Some(true));
+
+        assert_eq!(
+            display.draw_sprite_masked(&bitmap, &mask[..1], 4, Point::zero()),
+            Err(Error::BufferSize)
+        );
+        done(display);
+    }
+
+    #[cfg(feature = "graphics")]
+    #[test]
+    fn draw_and_flush() {
+        use embedded_graphics::primitives::{Line, PrimitiveStyle};
+
+        let mut data = [0; 12];
+        data[0] = 0xA0;
+        // Exactly one flush, with the window because it's unknown
+        let mut display = Expect::default()
+            .command(Instruction::CASET, &[0, 1])
+            .command(Instruction::RASET, &[0, 1])
+            .command(Instruction::RAMWR, &[])
+            .data(&data)
+            .display();
+        display.ram_window = None;
+        let line = Line::new(Point::new(0, 0), Point::new(1, 0))
+            .into_styled(PrimitiveStyle::with_stroke(Rgb565::BLACK, 1));
+        display.draw_and_flush(&line).unwrap();
+        assert!(!display.needs_flush());
+        done(display);
+    }
+
+    #[test]
+    fn flush_interlaced() {
+        // A pattern that is different in every row, 4 rows of 2 cells
+        let mut framebuffer = [[[0; 3]; 2]; 4];
+        for (row, cells) in framebuffer.iter_mut().enumerate() {
+            cells[0] = [row as u8, 0x11, 0x22];
+            cells[1] = [0x33, row as u8, 0x44];
+        }
+        let full = framebuffer.as_flattened().as_flattened();
+
+        // Even rows first, then odd ones, together the data of a full flush
+        let mut expect = Expect::default()
+            .command(Instruction::CASET, &[0, 1])
+            .command(Instruction::RASET, &[0, 3]);
+        for row in [0, 2, 1, 3] {
+            expect = expect
+                .command(Instruction::RASET, &[row as u8, row as u8])
+                .command(Instruction::RAMWR, &[])
+                .data(&full[row * 6..(row + 1) * 6]);
+        }
+        expect = expect
+            .command(Instruction::CASET, &[0, 1])
+            .command(Instruction::RASET, &[0, 3]);
+        let mut display = ST7306Builder::new()
+            .dimensions(24, 8)
+            .build::<_, _, _, _, 2, 4>(
+                SpiMock::new(&expect.spi),
+                PinMock::new(&expect.dc),
+                PinMock::new(&expect.cs),
+                PinMock::new(&[]),
+            )
+            .unwrap();
+        display.framebuffer = framebuffer;
+        display.flush_interlaced().unwrap();
+        assert!(!display.needs_flush());
+        done(display);
+    }
+
+    #[test]
+    fn auto_power() {
+        let mut display = Expect::default()
+            .command(Instruction::HPM, &[])
+            .command(Instruction::LPM, &[])
+            .display();
+        display.power_mode = PowerMode::Lpm;
+        display.set_auto_power_timeout(3);
+
+        // Activity switches to HPM at once
+        display.auto_power(true).unwrap();
+        assert_eq!(display.power_mode(), PowerMode::Hpm);
+
+        // Activity within the timeout starts the count again
+        for activity in [false, false, true, false, false] {
+            display.auto_power(activity).unwrap();
+            assert_eq!(display.power_mode(), PowerMode::Hpm);
+        }
+
+        // The third idle call in a row goes to LPM, and it stays there
+        display.auto_power(false).unwrap();
+        assert_eq!(display.power_mode(), PowerMode::Lpm);
+        display.auto_power(false).unwrap();
+        assert_eq!(display.power_mode(), PowerMode::Lpm);
+        done(display);
+    }
+
+    #[test]
+    fn set_data_order() {
+        // Only the DO bit changes
+        let mut display = Expect::default()
+            .command(Instruction::MADCTL, &[0b01000000])
+            .command(Instruction::MADCTL, &[0b01001000])
+            .display();
+        display.set_data_order(false).unwrap();
+        assert!(!display.madctl().data_order);
+        display.set_data_order(true).unwrap();
+        assert_eq!(display.madctl(), MadctlFlags::default());
+        done(display);
+    }
+
+    #[cfg(feature = "graphics")]
+    #[test]
+    fn partial_flush_break_even() {
+        let display = ST7306Builder::new()
+            .dimensions(300, 4)
+            .build::<_, _, _, _, 25, 2>(
+                SpiMock::new(&[]),
+                PinMock::new(&[]),
+                PinMock::new(&[]),
+                PinMock::new(&[]),
+            )
+            .unwrap();
+        let full = full_flush_bytes::<25, 2>();
+        assert_eq!(full, 151);
+
+        // 22 columns of cells in both rows are the most that are cheaper
+        assert!(partial_flush_bytes(22, 2) < full);
+        assert!(partial_flush_bytes(23, 2) >= full);
+        let area = |width| Rectangle::new(Point::zero(), Size::new(width, 4));
+        assert!(display.partial_flush_worthwhile(area(22 * 12)));
+        assert!(!display.partial_flush_worthwhile(area(22 * 12 + 1)));
+        assert!(!display.partial_flush_worthwhile(area(23 * 12)));
+
+        // Touching a cell counts like covering it
+        let unaligned = Rectangle::new(Point::new(11, 1), Size::new(22 * 12 - 10, 2));
+        assert!(!display.partial_flush_worthwhile(unaligned));
+        done(display);
+    }
+
+    #[test]
+    fn set_pixels_dirty_bounding_box() {
+        let mut display = ST7306Builder::new()
+            .dimensions(300, 8)
+            .build::<_, _, _, _, 25, 4>(
+                SpiMock::new(&[]),
+                PinMock::new(&[]),
+                PinMock::new(&[]),
+                PinMock::new(&[]),
+            )
+            .unwrap();
+        display.dirty = None;
+        let points = [
+            (13, 2, true),
+            (40, 5, true),
+            // Outside of the display, skipped
+            (300, 0, true),
+            (0, 8, true),
+        ];
+        display.set_pixels(points, false).unwrap();
+        assert_eq!(
+            display.dirty,
+            Some(AddrWindow {
+                col_start: 1,
+                col_end: 3,
+                row_start: 1,
+                row_end: 2,
+            })
+        );
+        assert_eq!(display.get_pixel(13, 2), Some(true));
+        assert_eq!(display.get_pixel(40, 5), Some(true));
+        done(display);
+    }
+
+    #[test]
+    fn flush_to_matches_flush_full() {
+        let mut sink_display = display(&[], &[], &[]);
+        for (x, y) in [(0, 0), (5, 1), (13, 2), (23, 3)] {
+            sink_display.set_pixel_on(x, y, true).unwrap();
+        }
+        let mut sent = Vec::new();
+        sink_display.flush_to(&mut |row: &[u8]| sent.extend_from_slice(row));
+        assert_eq!(sent.len(), 12);
+        // Nothing is sent and the framebuffer is still dirty
+        assert!(sink_display.needs_flush());
+
+        // The same bytes that a real flush sends after RAMWR
+        let mut display = Expect::default()
+            .command(Instruction::RAMWR, &[])
+            .data(&sent)
+            .display();
+        display.framebuffer = sink_display.framebuffer;
+        display.flush_full().unwrap();
+        done(display);
+        done(sink_display);
+    }
+
+    #[cfg(feature = "graphics")]
+    #[test]
+    fn blit_binary_framebuf() {
+        let mut display = display(&[], &[], &[]);
+        // 10x3 pixels, rows padded to 2 bytes
+        let fb = [
+            0b11000001, 0b01000000, //
+            0b00100000, 0b00000000, //
+            0b11111111, 0b11000000,
+        ];
+        // Sticks out at the right and the bottom
+        display
+            .blit_binary_framebuf(&fb, 10, 3, Point::new(20, 2))
+            .unwrap();
+        let black: Vec<_> = (0..4)
+            .flat_map(|y| (0..24).map(move |x| (x, y)))
+            .filter(|&(x, y)| display.get_pixel(x, y) == Some(true))
+            .collect();
+        assert_eq!(black, [(20, 2), (21, 2), (22, 3)]);
+
+        assert_eq!(
+            display.blit_binary_framebuf(&fb[..5], 10, 3, Point::zero()),
+            Err(Error::BufferSize)
+        );
+        done(display);
+    }
+
+    #[cfg(feature = "graphics")]
+    #[test]
+    fn draw_rotated_line() {
+        use embedded_graphics::primitives::{Line, PrimitiveStyle};
+
+        // A line two pixels to the right of the origin, rotated clockwise
+        let origin = Point::new(12, 1);
+        let line = Line::new(origin, origin + Point::new(1, 0))
+            .into_styled(PrimitiveStyle::with_stroke(Rgb565::BLACK, 1));
+        for (rotation, end) in [
+            (Rotation90::Deg0, (13, 1)),
+            (Rotation90::Deg90, (12, 2)),
+            (Rotation90::Deg180, (11, 1)),
+            (Rotation90::Deg270, (12, 0)),
+        ] {
+            let mut display = display(&[], &[], &[]);
+            display.draw_rotated(&line, origin, rotation).unwrap();
+            let mut black: Vec<_> = (0..4)
+                .flat_map(|y| (0..24).map(move |x| (x, y)))
+                .filter(|&(x, y)| display.get_pixel(x, y) == Some(true))
+                .collect();
+            black.sort();
+            let mut expected = [(12, 1), end];
+            expected.sort();
+            assert_eq!(black, expected, "{:?}", rotation);
+            done(display);
+        }
+    }
+
+    #[test]
+    fn new_and_probe() {
+        use embedded_hal_mock::eh0::delay::NoopDelay;
+
+        let expect = Expect::default().command(Instruction::NOP, &[]);
+        let reset = pin_states(&[State::High, State::Low, State::High]);
+        let display: MockDisplay = ST7306::new_and_probe(
+            SpiMock::new(&expect.spi),
+            PinMock::new(&expect.dc),
+            PinMock::new(&expect.cs),
+            PinMock::new(&reset),
+            &mut NoopDelay::new(),
+            ST7306Builder::new().dimensions(24, 4),
+        )
+        .unwrap();
+        assert!(display.is_sleeping());
+        done(display);
+    }
+
+    #[test]
+    fn new_and_probe_id() {
+        use embedded_hal_mock::eh0::delay::NoopDelay;
+
+        let config = ST7306Builder::new().dimensions(24, 4);
+        let reset = pin_states(&[State::High, State::Low, State::High]);
+        // 0x85, 0x85, 0x52 after the dummy bit
+        let expect = Expect::default().read(Instruction::RDDID, &[0x42, 0xC2, 0xA9, 0x00]);
+        let display: MockDisplay = ST7306::new_and_probe_id(
+            SpiMock::new(&expect.spi),
+            PinMock::new(&expect.dc),
+            PinMock::new(&expect.cs),
+            PinMock::new(&reset),
+            &mut NoopDelay::new(),
+            config,
+        )
+        .unwrap();
+        done(display);
+
+        // Nothing connected to MISO
+        for reply in [[0x00; 4], [0xFF; 4]] {
+            let expect = Expect::default().read(Instruction::RDDID, &reply);
+            let mut spi = SpiMock::new(&expect.spi);
+            let mut dc = PinMock::new(&expect.dc);
+            let mut cs = PinMock::new(&expect.cs);
+            let mut rst = PinMock::new(&reset);
+            let result: Result<MockDisplay, _> = ST7306::new_and_probe_id(
+                spi.clone(),
+                dc.clone(),
+                cs.clone(),
+                rst.clone(),
+                &mut NoopDelay::new(),
+                config,
+            );
+            assert!(matches!(result, Err(Error::NoResponse)));
+            spi.done();
+            dc.done();
+            cs.done();
+            rst.done();
+        }
+    }
+
+    #[test]
+    fn set_orientation_origin_pixel() {
+        let mut display = display(&[], &[], &[]);
+        for (orientation, (row, col, byte, mask)) in [
+            (Orientation::Portrait, (0, 0, 0, 0x80)),
+            (Orientation::Landscape, (0, 1, 2, 0x02)),
+            (Orientation::PortraitSwapped, (1, 1, 2, 0x01)),
+            (Orientation::LandscapeSwapped, (1, 0, 0, 0x40)),
+        ] {
+            display.set_orientation(&orientation).unwrap();
+            display.clear_buffer(false);
+            display.set_pixel(0, 0, 0).unwrap();
+            let mut expected = [[[0u8; 3]; 2]; 2];
+            expected[row][col][byte] = mask;
+            assert_eq!(display.framebuffer(), &expected, "{orientation:?}");
+        }
+        done(display);
+    }
+
+    #[cfg(feature = "async")]
+    type AsyncDisplay<const COLS: usize, const ROWS: usize> =
+        asynch::ST7306<Eh1SpiMock<u8>, Eh1PinMock, Eh1PinMock, COLS, ROWS>;
+
+    /// Poll a future to completion, the async mocks never have to wait
+    #[cfg(feature = "async")]
+    fn block_on<F: core::future::Future>(future: F) -> F::Output {
+        let mut future = core::pin::pin!(future);
+        let mut cx = core::task::Context::from_waker(core::task::Waker::noop());
+        loop {
+            if let core::task::Poll::Ready(output) = future.as_mut().poll(&mut cx) {
+                return output;
+            }
+        }
+    }
+
+    /// Expected bus traffic of the async driver, which has no CS pin
+    #[cfg(feature = "async")]
+    #[derive(Default)]
+    struct AsyncExpect {
+        spi: Vec<Eh1SpiTransaction<u8>>,
+        dc: Vec<Eh1PinTransaction>,
+    }
+
+    #[cfg(feature = "async")]
+    impl AsyncExpect {
+        /// A command as sent by [`asynch::ST7306::write_command()`]
+        fn command(mut self, command: Instruction, params: &[u8]) -> Self {
+            self.dc.push(Eh1PinTransaction::set(Eh1State::Low));
+            self.write(&[command as u8]);
+            if !params.is_empty() {
+                self = self.data(params);
+            }
+            self
+        }
+
+        /// Data after setting the DC pin high, in one SPI write
+        fn data(mut self, data: &[u8]) -> Self {
+            self.dc.push(Eh1PinTransaction::set(Eh1State::High));
+            self.write(data);
+            self
+        }
+
+        /// More data without setting the DC pin again
+        fn more_data(mut self, data: &[u8]) -> Self {
+            self.write(data);
+            self
+        }
+
+        /// One write of the SpiDevice, in its own transaction
+        fn write(&mut self, data: &[u8]) {
+            self.spi.push(Eh1SpiTransaction::transaction_start());
+            self.spi.push(Eh1SpiTransaction::write_vec(data.to_vec()));
+            self.spi.push(Eh1SpiTransaction::transaction_end());
+        }
+
+        fn display<const COLS: usize, const ROWS: usize>(
+            &self,
+            rst: &[Eh1PinTransaction],
+            config: ST7306Builder,
+        ) -> AsyncDisplay<COLS, ROWS> {
+            asynch::ST7306::new(
+                Eh1SpiMock::new(&self.spi),
+                Eh1PinMock::new(&self.dc),
+                Eh1PinMock::new(rst),
+                config,
+            )
+            .unwrap()
+        }
+    }
+
+    /// Check that all expected async transactions happened
+    #[cfg(feature = "async")]
+    fn async_done<const COLS: usize, const ROWS: usize>(mut display: AsyncDisplay<COLS, ROWS>) {
+        display.spi.done();
+        display.dc.done();
+        display.rst.done();
+    }
+
+    #[cfg(feature = "async")]
+    #[test]
+    fn async_init() {
+        let voltage = VoltageConfig::default();
+        let tuning = TuningConfig::default();
+        // The same sequence as the blocking init
+        let expect = AsyncExpect::default()
+            .command(Instruction::SWRESET, &[])
+            .command(Instruction::NVMLOADCTRL, &[0b10001, 0])
+            .command(Instruction::BSTEN, &[0x01])
+            .command(Instruction::GCTRL, &voltage.gate)
+            .command(Instruction::VSHPCTRL, &voltage.vshp)
+            .command(Instruction::VSLPCTRL, &voltage.vslp)
+            .command(Instruction::VSHNCTRL, &voltage.vshn)
+            .command(Instruction::VSLNCTRL, &voltage.vsln)
+            .command(Instruction::OSCSET, &tuning.oscillator)
+            .command(Instruction::FRCTRL, &[0x12])
+            .command(Instruction::GTUPEQH, &tuning.hpm_eq)
+            .command(Instruction::GTUPEQL, &tuning.lpm_eq)
+            .command(Instruction::SOUEQ, &[tuning.source_eq])
+            .command(Instruction::GATESET, &[1])
+            .command(Instruction::SLPOUT, &[])
+            .command(Instruction::LOWPOWER, &[0xC1, 0x4A, 0x26])
+            .command(Instruction::VSHLSEL, &[0x00])
+            .command(Instruction::MADCTL, &[MADCTL_DEFAULT])
+            .command(Instruction::DTFORM, &[0x11])
+            .command(Instruction::GAMAMS, &[0x20])
+            .command(Instruction::PNLSET, &[0x29])
+            .command(Instruction::CASET, &[0, 1])
+            .command(Instruction::RASET, &[0, 1])
+            .command(Instruction::AUTOPWRCTRL, &[0x7F])
+            .command(Instruction::TEOFF, &[])
+            .command(Instruction::LPM, &[])
+            .command(Instruction::INVOFF, &[])
+            .command(Instruction::DISPON, &[]);
+        let rst = [Eh1State::High, Eh1State::Low, Eh1State::High].map(Eh1PinTransaction::set);
+        let mut display: AsyncDisplay<2, 2> =
+            expect.display(&rst, ST7306Builder::new().dimensions(24, 4));
+        let mut delay = Eh1NoopDelay::new();
+
+        block_on(display.init(&mut delay)).unwrap();
+        let buffer = display.buffer();
+        assert!(buffer.is_initialized());
+        assert!(!buffer.is_sleeping());
+        assert!(buffer.is_on());
+        assert_eq!(buffer.power_mode(), PowerMode::Lpm);
+        assert_eq!(buffer.ram_window, Some(buffer.addr_window));
+        assert!(buffer.needs_flush());
+        assert!(matches!(
+            block_on(display.init(&mut delay)),
+            Err(Error::AlreadyInitialized)
+        ));
+        async_done(display);
+    }
+
+    #[cfg(feature = "async")]
+    #[test]
+    fn async_flush() {
+        // The window isn't known yet, so the full flush sends it, in
+        // chunks of max_transfer
+        let expect = AsyncExpect::default()
+            .command(Instruction::CASET, &[0, 24])
+            .command(Instruction::RASET, &[0, 1])
+            .command(Instruction::RAMWR, &[])
+            .data(&[0; 64])
+            .more_data(&[0; 64])
+            .more_data(&[0; 22])
+            // Only the changed cell, then the full window again
+            .command(Instruction::CASET, &[1, 1])
+            .command(Instruction::RASET, &[1, 1])
+            .command(Instruction::RAMWR, &[])
+            .data(&[0b00000100, 0, 0])
+            .command(Instruction::CASET, &[0, 24])
+            .command(Instruction::RASET, &[0, 1]);
+        let config = ST7306Builder::new().dimensions(300, 4).max_transfer(64);
+        let mut display: AsyncDisplay<25, 2> = expect.display(&[], config);
+
+        block_on(display.flush()).unwrap();
+        assert!(!display.buffer().needs_flush());
+        // Nothing changed
+        block_on(display.flush()).unwrap();
+
+        display.buffer_mut().set_pixel(14, 3, 0).unwrap();
+        block_on(display.flush()).unwrap();
+        assert!(!display.buffer().needs_flush());
+        assert_eq!(
+            display.buffer().ram_window,
+            Some(display.buffer().addr_window)
+        );
+        async_done(display);
+    }
+
+    #[test]
+    fn voltage_out_of_range() {
+        assert!(VoltageConfig::default().in_range());
+        let source = VoltageConfig {
+            vsln: [0x35, 0x35, 0x40, 0x35],
+            ..VoltageConfig::default()
+        };
+        let gate = VoltageConfig {
+            gate: [0x10, 0x02],
+            ..VoltageConfig::default()
+        };
+        let mut spi = SpiMock::new(&[]);
+        let mut pin = PinMock::new(&[]);
+        for voltage in [source, gate] {
+            let built: Result<MockDisplay, _> = ST7306Builder::new()
+                .dimensions(24, 4)
+                .voltage(voltage)
+                .build(spi.clone(), pin.clone(), pin.clone(), pin.clone());
+            assert!(matches!(built, Err(ConfigError::VoltageOutOfRange)));
+        }
+        spi.done();
+        pin.done();
+
+        let mut display = display(&[], &[], &[]);
+        assert_eq!(display.validate_config(), Ok(()));
+        display.voltage = source;
+        assert_eq!(
+            display.validate_config(),
+            Err(ConfigError::VoltageOutOfRange)
+        );
+        done(display);
+    }
+
+    #[test]
+    fn load_config_from_nvm() {
+        let trim = VoltageConfig {
+            gate: [0x09, 0x03],
+            vshp: [0x0C; 4],
+            vslp: [0x22; 4],
+            vshn: [0x28; 4],
+            vsln: [0x34; 4],
+        };
+        let mut display = Expect::default()
+            .command(Instruction::NVMRDEN, &[NVM_ENABLE])
+            // 0x09, 0x03, 0x0C, 0x22, 0x28, 0x34 after the dummy bit
+            .read(
+                Instruction::NVMRD,
+                &[0x04, 0x81, 0x86, 0x11, 0x14, 0x1A, 0x00],
+            )
+            .command(Instruction::NVMRDEN, &[NVM_DISABLE])
+            .command(Instruction::NVMRDEN, &[NVM_ENABLE])
+            // VSLN of 0x40 is out of range
+            .read(
+                Instruction::NVMRD,
+                &[0x04, 0x81, 0x86, 0x11, 0x14, 0x20, 0x00],
+            )
+            .command(Instruction::NVMRDEN, &[NVM_DISABLE])
+            .display();
+        assert_eq!(display.load_config_from_nvm(), Ok(trim));
+        assert_eq!(display.voltage, trim);
+        assert_eq!(
+            display.load_config_from_nvm(),
+            Err(Error::Config(ConfigError::VoltageOutOfRange))
+        );
+        assert_eq!(display.voltage, trim);
+        done(display);
+    }
+
+    #[test]
+    fn brightness_and_contrast_add_up() {
+        // VSHP moves by both levels, VSHN only by brightness and VSLP only
+        // by contrast
+        let mut display = Expect::default()
+            .command(Instruction::VSHPCTRL, &[0x11; 4])
+            .command(Instruction::VSHNCTRL, &[0x2D; 4])
+            .command(Instruction::VSHPCTRL, &[0x17; 4])
+            .command(Instruction::VSLPCTRL, &[0x1D; 4])
+            .command(Instruction::VSHPCTRL, &[0x11; 4])
+            .command(Instruction::VSHNCTRL, &[VSHN_DEFAULT; 4])
+            .display();
+        display.set_brightness(255).unwrap();
+        display.set_contrast(255).unwrap();
+        display.set_brightness(128).unwrap();
+        done(display);
+    }
+
+    #[test]
+    fn set_tuning_values() {
+        let mut display = Expect::default()
+            .command(Instruction::GTUPEQH, &[1; 10])
+            .command(Instruction::GTUPEQL, &[2; 8])
+            .display();
+        display.set_hpm_eq([1; 10]).unwrap();
+        display.set_lpm_eq([2; 8]).unwrap();
+
+        let tuning = TuningConfig {
+            frame_interval: 4,
+            ..display.tuning
+        };
+        assert_eq!(
+            display.set_tuning(tuning),
+            Err(ConfigError::InvalidFrameInterval)
+        );
+        assert_eq!(display.tuning.frame_interval, 2);
+        assert_eq!(display.tuning.hpm_eq, [1; 10]);
+        assert_eq!(display.set_tuning(TuningConfig::default()), Ok(()));
+        assert_eq!(display.tuning, TuningConfig::default());
+        done(display);
+    }
+
+    #[test]
+    fn rle_write_count() {
+        let mut display = ST7306Builder::new()
+            .dimensions(300, 400)
+            .build::<_, _, _, _, 25, 200>(
+                SpiMock::new(&[]),
+                PinMock::new(&[]),
+                PinMock::new(&[]),
+                PinMock::new(&[]),
+            )
+            .unwrap();
+        let cells: usize = 25 * 200;
+        // One write per 16 cells of a blank image, 48 bytes each
+        assert_eq!(display.rle_write_count(), cells.div_ceil(RLE_CHUNK_CELLS));
+        assert_eq!(display.rle_write_count(), (cells * 3).div_ceil(48));
+
+        // Every cell differs from its neighbours, one write per cell
+        for (i, cell) in display
+            .framebuffer
+            .as_flattened_mut()
+            .iter_mut()
+            .enumerate()
+        {
+            *cell = [(i % 2) as u8; 3];
+        }
+        assert_eq!(display.rle_write_count(), cells);
+        // A plain flush with the default max_transfer takes a single write
+        assert_eq!((cells * 3).div_ceil(display.max_transfer), 1);
+        done(display);
+    }
+
+    #[cfg(feature = "double-buffer")]
+    #[test]
+    fn present_without_new_frame() {
+        let mut display = Expect::default().display();
+        display.dirty = None;
+        display.framebuffer_mut()[1][1] = [0x80, 0, 0];
+
+        // The back buffer wasn't written, so the front one stays
+        display.present().unwrap();
+        assert_eq!(display.generation(), 0);
+        assert!(!display.needs_flush());
+        assert_eq!(display.get_pixel(12, 2), Some(true));
+
+        // An identical frame swaps, but doesn't count as a change
+        display.back_buffer_mut()[1][1] = [0x80, 0, 0];
+        display.present().unwrap();
+        assert_eq!(display.generation(), 0);
+        assert!(!display.needs_flush());
+        done(display);
+    }
+
+    #[test]
+    fn power_on_gray4_format() {
+        use embedded_hal_mock::eh0::delay::NoopDelay;
+
+        let expect = Expect::default()
+            .command(Instruction::SLPOUT, &[])
+            .command(Instruction::LOWPOWER, &[0xC1, 0x4A, 0x26])
+            .command(Instruction::VSHLSEL, &[0x00])
+            .command(Instruction::MADCTL, &[MADCTL_DEFAULT])
+            // 2 bit pixels and 4 gray levels
+            .command(Instruction::DTFORM, &[0x10])
+            .command(Instruction::GAMAMS, &[0x00])
+            .command(Instruction::PNLSET, &[0x29])
+            .command(Instruction::CASET, &[0, 0])
+            .command(Instruction::RASET, &[0, 1])
+            .command(Instruction::AUTOPWRCTRL, &[0x7F])
+            .command(Instruction::TEOFF, &[])
+            .command(Instruction::LPM, &[])
+            .command(Instruction::INVOFF, &[])
+            .command(Instruction::DISPON, &[]);
+        let mut display: MockDisplay = ST7306Builder::new()
+            .dimensions(12, 4)
+            .color_mode(ColorMode::Gray4)
+            .build(
+                SpiMock::new(&expect.spi),
+                PinMock::new(&expect.dc),
+                PinMock::new(&expect.cs),
+                PinMock::new(&[]),
+            )
+            .unwrap();
+        display.power_on(&mut NoopDelay::new()).unwrap();
+        done(display);
     }
 }
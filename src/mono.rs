@@ -0,0 +1,32 @@
+//! The two colors the panel can show, for the Rgb565 [`DrawTarget`] impl
+//!
+//! [`crate::ST7306`] draws [`Rgb565`] colors and turns them black or white
+//! by their brightness, so a mid gray can end up on either side. These
+//! constants are far from the threshold and always map to the same pixel.
+//!
+//! ```ignore
+//! use st7306::mono;
+//!
+//! Rectangle::new(Point::zero(), Size::new(24, 2))
+//!     .into_styled(PrimitiveStyle::with_fill(mono::BLACK))
+//!     .draw(&mut display)?;
+//! ```
+//!
+//! [`DrawTarget`]: embedded_graphics::draw_target::DrawTarget
+
+use embedded_graphics::pixelcolor::{Rgb565, RgbColor};
+
+/// Draws a black pixel
+pub const BLACK: Rgb565 = Rgb565::BLACK;
+
+/// Draws a white pixel
+pub const WHITE: Rgb565 = Rgb565::WHITE;
+
+/// The color that turns a pixel on (black) or off (white)
+pub const fn mono_color(on: bool) -> Rgb565 {
+    if on {
+        BLACK
+    } else {
+        WHITE
+    }
+}